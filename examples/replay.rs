@@ -0,0 +1,39 @@
+//! Plays a replay file headless and prints every `GameState` transition. Data
+//! files are loaded from the working directory, like the game does.
+//!
+//! ```text
+//! cargo run --example replay -- run.replay
+//! ```
+
+use centsyl::{game, replay::Replay, resources::LevelStats};
+
+fn main() {
+    let path = std::env::args().nth(1).expect("usage: replay <file>");
+
+    let replay = Replay::load(&path).expect("failed to load replay");
+    println!(
+        "{}: version {}, seed {}, {} ticks",
+        path,
+        replay.game_version,
+        replay.seed,
+        replay.len()
+    );
+    let changed = replay.changed_data(&game::data_files());
+    if !changed.is_empty() {
+        println!("changed since recording: {}", changed.join(", "));
+    }
+
+    let mut sim = replay.start().expect("invalid seed in replay");
+    let mut state = sim.game_state();
+    println!("{:>8} {:?}", sim.tick, state);
+
+    for input in replay.inputs() {
+        sim.step(&input);
+
+        if sim.game_state() != state {
+            state = sim.game_state();
+            let level = sim.resources.get::<LevelStats>().unwrap().level;
+            println!("{:>8} {:?} (level {})", sim.tick, state, level);
+        }
+    }
+}
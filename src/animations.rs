@@ -0,0 +1,398 @@
+//! Sprite animation clips, loaded from a JSON list such as the built-in
+//! `animations.json`:
+//!
+//! ```json
+//! [
+//!     {
+//!         "kind": "Player",
+//!         "actions": ["Moving"],
+//!         "facings": ["None", "Left", "Right"],
+//!         "frames": [{ "x": 38, "y": 1, "ticks": 75 }, { "x": 6, "y": 1, "ticks": 75 }],
+//!         "loop": "Loop",
+//!         "flip": ["Right"]
+//!     }
+//! ]
+//! ```
+//!
+//! `kind` is an `EntityType` without data, or a monster id. A clip plays for
+//! the `actions` and `facings` listed, all of them when left out, and no two
+//! clips may share a kind, action and facing. Frames are cells of the sprite
+//! sheet, each held for `ticks`. `loop` is `Loop` (the default), `Once`, which
+//! stays on the last frame, or `PingPong`, which runs back and forth. `flip`
+//! lists the facings the clip is drawn mirrored for.
+//!
+//! In place of `frames` a clip can name a `tag` of the Aseprite sprite sheet
+//! together with a `cell`, e.g. `"tag": "walk", "cell": { "x": 6, "y": 1 }`.
+//! The clip then shows that cell of every frame the tag runs over, counted
+//! from the frame's top left corner, for the frame's duration. The tag also
+//! gives the loop mode unless `loop` is set.
+//!
+//! Monsters without clips of their own play the frames from their
+//! `monsters::MonsterDef`, mirrored facing right like the built-in clips.
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    components::{types, AnimationState, EntityType},
+    data,
+    monsters::Monsters,
+};
+
+/// A sprite sheet cell, shown for `ticks` when animated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Frame {
+    pub x: u8,
+    pub y: u8,
+    #[serde(default)]
+    pub ticks: i16,
+}
+
+impl Frame {
+    /// Whether the cell is on the sprite sheet, which is 96 cells across and
+    /// 32 down.
+    pub fn on_sheet(&self) -> bool {
+        self.x < 96 && self.y < 32
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum LoopMode {
+    #[default]
+    Loop,
+    Once,
+    PingPong,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Clip {
+    pub frames: Vec<Frame>,
+    pub loop_mode: LoopMode,
+    /// facings drawn mirrored left to right
+    pub flip: Vec<types::Facing>,
+}
+
+/// A run of whole frames of the sprite sheet, such as an Aseprite tag. Each
+/// frame is given by the cell at its top left corner and held for its
+/// `ticks`. Every frame is `size` cells across and down.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag {
+    pub frames: Vec<Frame>,
+    pub loop_mode: LoopMode,
+    pub size: (u8, u8),
+}
+
+impl Tag {
+    /// The cell `x`, `y` of every frame, or `None` if the frames don't have
+    /// that cell.
+    pub fn cell_frames(&self, x: u8, y: u8) -> Option<Vec<Frame>> {
+        if x >= self.size.0 || y >= self.size.1 {
+            return None;
+        }
+
+        self.frames
+            .iter()
+            .map(|f| {
+                Some(Frame {
+                    x: f.x.checked_add(x)?,
+                    y: f.y.checked_add(y)?,
+                    ticks: f.ticks,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Tags by name, such as those of an `aseprite::AsepriteSheet`.
+pub type Tags = HashMap<String, Tag>;
+
+impl Clip {
+    // frames played before the clip starts over, or stops for `Once`
+    fn steps(&self) -> usize {
+        match self.loop_mode {
+            LoopMode::PingPong if self.frames.len() > 1 => 2 * self.frames.len() - 2,
+            _ => self.frames.len(),
+        }
+    }
+
+    fn frame(&self, step: usize) -> Frame {
+        let last = self.frames.len() - 1;
+        self.frames[if step > last { 2 * last - step } else { step }]
+    }
+}
+
+// one entry of the JSON list
+#[derive(Deserialize)]
+struct ClipEntry {
+    kind: String,
+    #[serde(default)]
+    actions: Vec<types::Action>,
+    #[serde(default)]
+    facings: Vec<types::Facing>,
+    #[serde(default)]
+    frames: Vec<Frame>,
+    /// a tag to take the frames and loop mode from, showing `cell`
+    tag: Option<String>,
+    cell: Option<Cell>,
+    #[serde(rename = "loop")]
+    loop_mode: Option<LoopMode>,
+    #[serde(default)]
+    flip: Vec<types::Facing>,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+struct Cell {
+    x: u8,
+    y: u8,
+}
+
+const ACTIONS: [types::Action; 2] = [types::Action::Stationary, types::Action::Moving];
+const FACINGS: [types::Facing; 5] = [
+    types::Facing::None,
+    types::Facing::Right,
+    types::Facing::Left,
+    types::Facing::Up,
+    types::Facing::Down,
+];
+
+/// The name clips are listed under for `typ`.
+pub fn kind_name(typ: &EntityType) -> &str {
+    match typ {
+        EntityType::Player => "Player",
+        EntityType::Monster(id) => id.as_str(),
+        EntityType::Projectile => "Projectile",
+        EntityType::Explosion => "Explosion",
+        EntityType::Health => "Health",
+        EntityType::Ammo => "Ammo",
+    }
+}
+
+#[derive(Debug)]
+pub enum AnimationError {
+    Io(PathBuf, io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for AnimationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnimationError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            AnimationError::Json(e) => write!(f, "{}", e),
+            AnimationError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AnimationError {}
+
+impl From<serde_json::Error> for AnimationError {
+    fn from(e: serde_json::Error) -> Self {
+        AnimationError::Json(e)
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> AnimationError {
+    AnimationError::Invalid(msg.into())
+}
+
+/// Every clip by kind, action and facing. Defaults to the built-in clips
+/// together with the built-in monsters.
+#[derive(Clone, Debug)]
+pub struct Animations {
+    clips: Vec<Clip>,
+    kinds: HashMap<String, HashMap<(types::Action, types::Facing), usize>>,
+}
+
+impl Default for Animations {
+    fn default() -> Self {
+        Animations::from_json(data::ANIMATIONS)
+            .expect("built-in animations.json is invalid")
+            .with_monsters(&Monsters::default())
+    }
+}
+
+impl Animations {
+    /// Loads clips that may name any of `tags`.
+    pub fn load(path: impl AsRef<Path>, tags: &Tags) -> Result<Self, AnimationError> {
+        let path = path.as_ref();
+        let json =
+            std::fs::read_to_string(path).map_err(|e| AnimationError::Io(path.to_owned(), e))?;
+        Animations::from_json_with_tags(&json, tags)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, AnimationError> {
+        Animations::from_json_with_tags(json, &Tags::new())
+    }
+
+    pub fn from_json_with_tags(json: &str, tags: &Tags) -> Result<Self, AnimationError> {
+        let entries: Vec<ClipEntry> = serde_json::from_str(json)?;
+
+        let mut animations = Animations {
+            clips: Vec::new(),
+            kinds: HashMap::new(),
+        };
+        for ClipEntry {
+            kind,
+            actions,
+            facings,
+            frames,
+            tag,
+            cell,
+            loop_mode,
+            flip,
+        } in entries
+        {
+            let clip = match (tag, cell) {
+                (Some(_), _) if !frames.is_empty() => {
+                    return Err(invalid(format!(
+                        "a {} clip has both frames and a tag",
+                        kind
+                    )));
+                }
+                (Some(tag), Some(Cell { x, y })) => {
+                    let tagged = tags
+                        .get(&tag)
+                        .ok_or_else(|| invalid(format!("{} uses an unknown tag {}", kind, tag)))?;
+                    let frames = tagged.cell_frames(x, y).ok_or_else(|| {
+                        invalid(format!(
+                            "{} cell {}, {} is outside the frames of tag {}",
+                            kind, x, y, tag
+                        ))
+                    })?;
+                    Clip {
+                        frames,
+                        loop_mode: loop_mode.unwrap_or(tagged.loop_mode),
+                        flip,
+                    }
+                }
+                (Some(tag), None) => {
+                    return Err(invalid(format!(
+                        "a {} clip uses tag {} without a cell",
+                        kind, tag
+                    )));
+                }
+                (None, Some(_)) => {
+                    return Err(invalid(format!("a {} clip has a cell but no tag", kind)));
+                }
+                (None, None) => Clip {
+                    frames,
+                    loop_mode: loop_mode.unwrap_or_default(),
+                    flip,
+                },
+            };
+
+            if clip.frames.is_empty() {
+                return Err(invalid(format!("a {} clip has no frames", kind)));
+            }
+            if let Some(f) = clip.frames.iter().find(|f| !f.on_sheet()) {
+                return Err(invalid(format!(
+                    "{} frame {}, {} is off the sprite sheet",
+                    kind, f.x, f.y
+                )));
+            }
+            if clip.frames.len() > 1 && clip.frames.iter().any(|f| f.ticks < 1) {
+                return Err(invalid(format!("a {} clip has frames without ticks", kind)));
+            }
+
+            let actions = if actions.is_empty() {
+                &ACTIONS[..]
+            } else {
+                &actions[..]
+            };
+            let facings = if facings.is_empty() {
+                &FACINGS[..]
+            } else {
+                &facings[..]
+            };
+            let keys = animations.kinds.entry(kind.clone()).or_default();
+            for &action in actions {
+                for &facing in facings {
+                    if keys
+                        .insert((action, facing), animations.clips.len())
+                        .is_some()
+                    {
+                        return Err(invalid(format!(
+                            "{} has two clips for {:?} facing {:?}",
+                            kind, action, facing
+                        )));
+                    }
+                }
+            }
+            animations.clips.push(clip);
+        }
+
+        Ok(animations)
+    }
+
+    /// Adds clips from the monsters' own frames wherever a monster has none.
+    pub fn with_monsters(mut self, monsters: &Monsters) -> Self {
+        for def in monsters.iter() {
+            for (action, frames) in [
+                (types::Action::Stationary, &def.frames.stationary),
+                (types::Action::Moving, &def.frames.moving),
+            ] {
+                let idx = self.clips.len();
+                let keys = self.kinds.entry(def.id.as_str().to_owned()).or_default();
+                let mut used = false;
+                for facing in FACINGS {
+                    if let Entry::Vacant(entry) = keys.entry((action, facing)) {
+                        entry.insert(idx);
+                        used = true;
+                    }
+                }
+                if used {
+                    self.clips.push(Clip {
+                        frames: frames.clone(),
+                        loop_mode: LoopMode::Loop,
+                        flip: vec![types::Facing::Right],
+                    });
+                }
+            }
+        }
+
+        self
+    }
+
+    /// The clip played for `typ` doing `action` facing `facing`, with its
+    /// index.
+    pub fn clip(
+        &self,
+        typ: &EntityType,
+        action: types::Action,
+        facing: types::Facing,
+    ) -> Option<(usize, &Clip)> {
+        let idx = *self.kinds.get(kind_name(typ))?.get(&(action, facing))?;
+        Some((idx, &self.clips[idx]))
+    }
+
+    /// Advances `anim` by a tick of clip `idx` and returns the frame to show.
+    /// Switching to another clip starts it from its first frame.
+    pub fn play(&self, idx: usize, anim: &mut AnimationState) -> Frame {
+        let clip = &self.clips[idx];
+        let steps = clip.steps();
+
+        if anim.clip != Some(idx) {
+            *anim = AnimationState {
+                countdown_timer: clip.frames[0].ticks,
+                current_frame: 0,
+                clip: Some(idx),
+            };
+        } else if steps > 1
+            && !(clip.loop_mode == LoopMode::Once && anim.current_frame == steps - 1)
+        {
+            anim.countdown_timer -= 1;
+            if anim.countdown_timer <= 0 {
+                anim.current_frame = (anim.current_frame + 1) % steps;
+                anim.countdown_timer += clip.frame(anim.current_frame).ticks;
+            }
+        }
+
+        clip.frame(anim.current_frame % steps)
+    }
+}
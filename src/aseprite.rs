@@ -0,0 +1,308 @@
+//! Sprite sheets exported from Aseprite together with their JSON data, e.g.
+//!
+//! ```sh
+//! aseprite -b isometric.aseprite --sheet sprites.png --data sprites.json \
+//!     --sheet-type horizontal --list-tags
+//! ```
+//!
+//! Both the hash and the array data format work. Each Aseprite frame is a
+//! whole picture of every sprite, so the built-in `isometric.aseprite` with
+//! its three 512 by 512 frames exports to a 1536 by 512 sheet. Frames have to
+//! be untrimmed, all the same size and lined up with the sheet's 16 by 16
+//! cells, and the sheet at least the 96 by 32 cells the map tiles and sprites
+//! are looked up in. Fully transparent pixels become black, which is what the
+//! game leaves out when drawing.
+//!
+//! Each frame tag becomes an `animations::Tag` named after it, running over
+//! its frames, each held for its duration rounded to ticks. A clip picks a
+//! cell to show from every frame. `reverse` and `pingpong_reverse` tags play
+//! their frames backwards, ping-pong tags play as `LoopMode::PingPong`, and
+//! other tags with a repeat count play as often as that and stop.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use image::{Rgb, RgbImage, RgbaImage};
+use serde::{
+    de::{MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{
+    animations::{Frame, LoopMode, Tag, Tags},
+    data::TICKS_PER_SECOND,
+    spritegrid::SpriteGrid,
+};
+
+const CELL_SIZE: u32 = 16;
+const MIN_COLS: u32 = 96;
+const MIN_ROWS: u32 = 32;
+
+#[derive(Deserialize)]
+struct SheetData {
+    frames: SheetFrames,
+    meta: Meta,
+}
+
+// the frames in the order they're listed, whichever data format
+struct SheetFrames(Vec<SheetFrame>);
+
+impl<'de> Deserialize<'de> for SheetFrames {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FramesVisitor;
+
+        impl<'de> Visitor<'de> for FramesVisitor {
+            type Value = SheetFrames;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a list or map of frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some(frame) = seq.next_element()? {
+                    frames.push(frame);
+                }
+                Ok(SheetFrames(frames))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut frames = Vec::new();
+                while let Some((_, frame)) = map.next_entry::<String, _>()? {
+                    frames.push(frame);
+                }
+                Ok(SheetFrames(frames))
+            }
+        }
+
+        deserializer.deserialize_any(FramesVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+struct SheetFrame {
+    frame: Rect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    /// milliseconds
+    duration: u32,
+}
+
+#[derive(Deserialize)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    image: String,
+    #[serde(rename = "frameTags", default)]
+    frame_tags: Vec<FrameTag>,
+}
+
+#[derive(Deserialize)]
+struct FrameTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: Direction,
+    repeat: Option<String>,
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Direction {
+    #[default]
+    Forward,
+    Reverse,
+    Pingpong,
+    PingpongReverse,
+}
+
+#[derive(Debug)]
+pub enum AsepriteError {
+    Io(PathBuf, io::Error),
+    Image(PathBuf, image::ImageError),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for AsepriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AsepriteError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            AsepriteError::Image(path, e) => write!(f, "{}: {}", path.display(), e),
+            AsepriteError::Json(e) => write!(f, "{}", e),
+            AsepriteError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AsepriteError {}
+
+impl From<serde_json::Error> for AsepriteError {
+    fn from(e: serde_json::Error) -> Self {
+        AsepriteError::Json(e)
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> AsepriteError {
+    AsepriteError::Invalid(msg.into())
+}
+
+/// A sprite sheet with the animation tags it was exported with.
+pub struct AsepriteSheet {
+    pub grid: SpriteGrid<Rgb<u8>, Vec<u8>>,
+    pub tags: Tags,
+}
+
+impl AsepriteSheet {
+    /// Loads the JSON data at `path` and the sheet image it names, relative
+    /// to it.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AsepriteError> {
+        let path = path.as_ref();
+        let json =
+            std::fs::read_to_string(path).map_err(|e| AsepriteError::Io(path.to_owned(), e))?;
+        let data: SheetData = serde_json::from_str(&json)?;
+
+        let image_path = path
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .join(&data.meta.image);
+        let image = image::open(&image_path)
+            .map_err(|e| AsepriteError::Image(image_path, e))?
+            .to_rgba8();
+
+        AsepriteSheet::from_data(data, image)
+    }
+
+    /// Reads the JSON data of a sheet that's already loaded as `image`.
+    pub fn from_json(json: &str, image: RgbaImage) -> Result<Self, AsepriteError> {
+        AsepriteSheet::from_data(serde_json::from_str(json)?, image)
+    }
+
+    fn from_data(data: SheetData, image: RgbaImage) -> Result<Self, AsepriteError> {
+        let (cols, rows) = (image.width() / CELL_SIZE, image.height() / CELL_SIZE);
+        if cols < MIN_COLS || rows < MIN_ROWS {
+            return Err(invalid(format!(
+                "the sheet is {} by {} cells, it needs at least {} by {}",
+                cols, rows, MIN_COLS, MIN_ROWS
+            )));
+        }
+
+        // the top left cell of every frame, and the size they all share
+        let mut frames = Vec::new();
+        let mut size = None;
+        for (i, f) in data.frames.0.iter().enumerate() {
+            let Rect { x, y, w, h } = f.frame;
+            if f.rotated || f.trimmed {
+                return Err(invalid(format!("frame {} is rotated or trimmed", i)));
+            }
+            if [x, y, w, h].iter().any(|v| v % CELL_SIZE != 0) || w == 0 || h == 0 {
+                return Err(invalid(format!(
+                    "frame {} isn't lined up with the sheet's {} by {} cells",
+                    i, CELL_SIZE, CELL_SIZE
+                )));
+            }
+            if x + w > image.width() || y + h > image.height() {
+                return Err(invalid(format!("frame {} runs off the sheet", i)));
+            }
+            match size {
+                None => size = Some((w, h)),
+                Some((first_w, first_h)) if (w, h) != (first_w, first_h) => {
+                    return Err(invalid(format!(
+                        "frame {} is {} by {}, frame 0 {} by {}",
+                        i, w, h, first_w, first_h
+                    )));
+                }
+                Some(_) => (),
+            }
+
+            // rounded, and never shorter than a tick
+            let ticks = ((f.duration * TICKS_PER_SECOND + 500) / 1000).max(1);
+            frames.push(Frame {
+                x: (x / CELL_SIZE).min(u8::MAX as u32) as u8,
+                y: (y / CELL_SIZE).min(u8::MAX as u32) as u8,
+                ticks: ticks.min(i16::MAX as u32) as i16,
+            });
+        }
+
+        let (w, h) = size.unwrap_or_default();
+        let size = (
+            (w / CELL_SIZE).min(u8::MAX as u32) as u8,
+            (h / CELL_SIZE).min(u8::MAX as u32) as u8,
+        );
+
+        let mut tags = Tags::new();
+        for tag in data.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(invalid(format!(
+                    "tag {} runs from frame {} to {} of {}",
+                    tag.name,
+                    tag.from,
+                    tag.to,
+                    frames.len()
+                )));
+            }
+            let mut tag_frames = frames[tag.from..=tag.to].to_vec();
+            if let Some(f) = tag_frames.iter().find(|f| !f.on_sheet()) {
+                return Err(invalid(format!(
+                    "tag {} has a frame at cell {}, {}, outside the {} by {} cells sprites are drawn from",
+                    tag.name, f.x, f.y, MIN_COLS, MIN_ROWS
+                )));
+            }
+            if matches!(
+                tag.direction,
+                Direction::Reverse | Direction::PingpongReverse
+            ) {
+                tag_frames.reverse();
+            }
+
+            let repeat = match tag.repeat.as_deref().map(str::parse::<usize>) {
+                None | Some(Ok(0)) => None,
+                Some(Ok(n)) => Some(n),
+                Some(Err(_)) => {
+                    return Err(invalid(format!("tag {} has a bad repeat count", tag.name)))
+                }
+            };
+            let loop_mode = match (tag.direction, repeat) {
+                (Direction::Pingpong | Direction::PingpongReverse, _) => LoopMode::PingPong,
+                (_, None) => LoopMode::Loop,
+                (_, Some(n)) => {
+                    tag_frames = tag_frames.repeat(n);
+                    LoopMode::Once
+                }
+            };
+
+            let run = Tag {
+                frames: tag_frames,
+                loop_mode,
+                size,
+            };
+            if tags.insert(tag.name.clone(), run).is_some() {
+                return Err(invalid(format!("tag {} is there twice", tag.name)));
+            }
+        }
+
+        // the game draws black as see-through
+        let mut rgb = RgbImage::new(image.width(), image.height());
+        for (x, y, p) in image.enumerate_pixels() {
+            if p[3] > 0 {
+                rgb.put_pixel(x, y, Rgb([p[0], p[1], p[2]]));
+            }
+        }
+
+        Ok(AsepriteSheet {
+            grid: SpriteGrid::new(rgb, CELL_SIZE, CELL_SIZE, rows, cols),
+            tags,
+        })
+    }
+}
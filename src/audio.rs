@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use log::warn;
+
+use crate::data::create_sound_map;
+
+pub trait AudioBackend: Send + Sync {
+    fn play(&self, tick: u64, sound_id: &str, volume: f32);
+}
+
+/// Plays sounds on the default output device through rodio.
+pub struct RodioAudio {
+    astream_handle: rodio::OutputStreamHandle,
+    sounds: HashMap<&'static str, Vec<u8>>,
+}
+
+impl RodioAudio {
+    /// Opens the default output device. The returned stream has to be kept
+    /// alive for as long as sounds should be heard.
+    pub fn try_default() -> Result<(Self, rodio::OutputStream), rodio::StreamError> {
+        let (astream, astream_handle) = rodio::OutputStream::try_default()?;
+
+        let audio = RodioAudio {
+            astream_handle,
+            sounds: create_sound_map(),
+        };
+
+        Ok((audio, astream))
+    }
+}
+
+impl AudioBackend for RodioAudio {
+    fn play(&self, _tick: u64, sound_id: &str, volume: f32) {
+        let Some(sound) = self.sounds.get(sound_id) else {
+            warn!("unknown sound: {}", sound_id);
+            return;
+        };
+
+        let file = std::io::Cursor::new(sound.clone());
+        match self.astream_handle.play_once(file) {
+            Ok(sink) => {
+                sink.set_volume(volume);
+                sink.detach();
+            }
+            Err(e) => warn!("failed to play {}: {}", sound_id, e),
+        }
+    }
+}
+
+/// Silently drops every sound, for headless runs and machines without audio.
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn play(&self, _tick: u64, _sound_id: &str, _volume: f32) {}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayedSound {
+    pub tick: u64,
+    pub sound_id: String,
+    pub volume: f32,
+}
+
+/// Logs every sound instead of playing it. Clones share the same log, so a
+/// test can keep one while the simulation owns the other.
+#[derive(Clone, Default)]
+pub struct RecordingAudio {
+    log: Arc<Mutex<Vec<PlayedSound>>>,
+}
+
+impl RecordingAudio {
+    pub fn played(&self) -> Vec<PlayedSound> {
+        self.log.lock().unwrap().clone()
+    }
+
+    pub fn played_ids(&self) -> Vec<String> {
+        self.log
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|p| p.sound_id.clone())
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.log.lock().unwrap().clear();
+    }
+}
+
+impl AudioBackend for RecordingAudio {
+    fn play(&self, tick: u64, sound_id: &str, volume: f32) {
+        self.log.lock().unwrap().push(PlayedSound {
+            tick,
+            sound_id: sound_id.to_owned(),
+            volume,
+        });
+    }
+}
@@ -1,19 +1,24 @@
 use legion::Entity;
+use serde::{Deserialize, Serialize};
+
+use crate::monsters::MonsterId;
 
 pub mod types {
-    #[derive(Clone, Copy, PartialEq, Default)]
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
     pub struct Pos<T> {
         pub x: T,
         pub y: T,
     }
 
-    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
     pub enum Action {
         Stationary,
         Moving,
     }
 
-    #[derive(Clone, Copy, Debug, PartialEq, Default)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
     pub enum Facing {
         #[default]
         None,
@@ -27,12 +32,11 @@ pub mod types {
     pub type DeltaPosition = Pos<f32>;
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum EntityType {
     Player,
-    MonsterA,
-    MonsterB,
-    MonsterC,
+    /// one of the `monsters::Monsters`
+    Monster(MonsterId),
     Projectile,
     Explosion,
     Health,
@@ -43,44 +47,57 @@ pub struct FixedScreenPos {
     pub x: i32,
     pub y: i32,
 }
+#[derive(Serialize, Deserialize)]
 pub struct ScreenDrawOffset {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(PartialEq, Eq, Clone, Copy, Default)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct MapPosition {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ActionState(pub types::Action);
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct RenderableSprite {
     pub sprite_x: u8,
     pub sprite_y: u8,
     pub facing: types::Facing,
+    /// drawn mirrored left to right
+    #[serde(default)]
+    pub flip: bool,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Default)]
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
 pub struct AnimationState {
     pub countdown_timer: i16,
     pub current_frame: usize,
+    /// the `animations::Animations` clip being played
+    #[serde(default)]
+    pub clip: Option<usize>,
 }
+#[derive(Serialize, Deserialize)]
 pub struct PlayerFlag;
 
+#[derive(Serialize, Deserialize)]
 pub struct ProjectileFlag {
     pub origin_entity: Entity,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct EnemyFlag;
 
+#[derive(Serialize, Deserialize)]
 pub struct PlayerViewportFlag;
 
+#[derive(Serialize, Deserialize)]
 pub struct OnlyVisibleInPlayerFOV;
 
+#[derive(Serialize, Deserialize)]
 pub struct UpdateViewshedsFlag;
 
 pub struct ActionTimer {
@@ -88,18 +105,20 @@ pub struct ActionTimer {
     pub increment_time: i32,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Moving {
     pub ticks_left: i32,
     pub total_ticks: i32,
     pub delta: types::DeltaPosition,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Viewshed {
     pub visible: Vec<MapPosition>,
     pub range: i32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct TimeToLive(pub i32);
 
 pub struct TextBlock {
@@ -108,20 +127,30 @@ pub struct TextBlock {
     pub alignment: embedded_graphics::text::Alignment,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct DealDamage {
     pub target: Entity,
     pub amount: i32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Score(pub i32);
+#[derive(Serialize, Deserialize)]
 pub struct Health(pub i32);
+#[derive(Serialize, Deserialize)]
 pub struct Ammo(pub i32);
 
+#[derive(Serialize, Deserialize)]
 pub struct MoveTimer {
     pub time_left: i32,
 }
 
+/// Counts down to the next hit while standing on a damaging tile.
+#[derive(Serialize, Deserialize)]
+pub struct HazardTimer {
+    pub time_left: i32,
+}
+
 /*
 #[derive(Default)]
 struct SampleFilter;
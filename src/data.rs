@@ -1,32 +1,56 @@
 use std::{collections::HashMap, io::Write};
 
-const HIT1: &[u8] = include_bytes!("../hit1.wav");
-const HIT2: &[u8] = include_bytes!("../hit2.wav");
-const HIT3: &[u8] = include_bytes!("../hit3.wav");
-const FIRE1: &[u8] = include_bytes!("../fire1.wav");
-const EXPLODE1: &[u8] = include_bytes!("../explode1.wav");
+/// Every sound by the name it's played with.
+pub const SOUNDS: &[(&str, &[u8])] = &[
+    ("hit1", include_bytes!("../hit1.wav")),
+    ("hit2", include_bytes!("../hit2.wav")),
+    ("hit3", include_bytes!("../hit3.wav")),
+    ("fire1", include_bytes!("../fire1.wav")),
+    ("explode1", include_bytes!("../explode1.wav")),
+];
 
 pub const SPRITES: &[u8] = include_bytes!("../isometric.png");
 
+// name and text of every prefab room, see `prefab::Prefab::parse`
+pub const PREFABS: &[(&str, &str)] = &[
+    ("arena", include_str!("../prefabs/arena.txt")),
+    ("ammo_cache", include_str!("../prefabs/ammo_cache.txt")),
+    ("shrine", include_str!("../prefabs/shrine.txt")),
+];
+
+/// The built-in monsters, see `monsters::Monsters`.
+pub const MONSTERS: &str = include_str!("../monsters.json");
+
+/// The built-in animation clips, see `animations::Animations`.
+pub const ANIMATIONS: &str = include_str!("../animations.json");
+
 pub const SCREEN_WIDTH: u32 = 320;
 pub const SCREEN_HEIGHT: u32 = 192;
 
+pub const TICKS_PER_SECOND: u32 = 60;
 pub const PLAYER_MOVE_TICKS: u32 = 8;
+/// Ticks between two hits from a damaging tile like lava.
+pub const HAZARD_DAMAGE_TICKS: i32 = TICKS_PER_SECOND as i32 / 2;
+
+pub const BINDINGS_FILE: &str = "bindings.json";
+pub const SAVE_FILE: &str = "centsyl.save";
+pub const LEVELS_FILE: &str = "levels.json";
+pub const MONSTERS_FILE: &str = "monsters.json";
+pub const ANIMATIONS_FILE: &str = "animations.json";
+/// Aseprite's JSON data for a sprite sheet replacing the built-in one.
+pub const SPRITE_SHEET_FILE: &str = "sprites.json";
 
 pub const MONSTER_A: &str = "MONSTER_A";
 
 fn convert_sound_to_vec(bytes: &[u8]) -> Vec<u8> {
     let mut hit_sound_data = Vec::new();
-    hit_sound_data.write(bytes).unwrap();
+    hit_sound_data.write_all(bytes).unwrap();
     hit_sound_data
 }
 
 pub fn create_sound_map() -> HashMap<&'static str, Vec<u8>> {
-    let mut map = HashMap::new();
-    map.insert("hit1", convert_sound_to_vec(HIT1));
-    map.insert("hit2", convert_sound_to_vec(HIT2));
-    map.insert("hit3", convert_sound_to_vec(HIT3));
-    map.insert("fire1", convert_sound_to_vec(FIRE1));
-    map.insert("explode1", convert_sound_to_vec(EXPLODE1));
-    map
+    SOUNDS
+        .iter()
+        .map(|&(name, bytes)| (name, convert_sound_to_vec(bytes)))
+        .collect()
 }
@@ -2,6 +2,7 @@ use image::Rgb;
 
 use super::spritegrid::SpriteGrid;
 
+#[allow(clippy::too_many_arguments)]
 pub fn blit_sprite(
     sprite_grid: &SpriteGrid<Rgb<u8>, Vec<u8>>,
     sprite_pos_x: u32,
@@ -12,16 +13,213 @@ pub fn blit_sprite(
     frame_width: u32,
     frame_height: u32,
     flip_y: bool,
+) {
+    blit_sprite_with(
+        sprite_grid,
+        sprite_pos_x,
+        sprite_pos_y,
+        x,
+        y,
+        frame,
+        frame_width,
+        frame_height,
+        flip_y,
+        |rgb| rgb,
+    );
+}
+
+/// Like `blit_sprite`, but greyed out and darkened, for things remembered
+/// rather than seen.
+#[allow(clippy::too_many_arguments)]
+pub fn blit_sprite_dimmed(
+    sprite_grid: &SpriteGrid<Rgb<u8>, Vec<u8>>,
+    sprite_pos_x: u32,
+    sprite_pos_y: u32,
+    x: i32,
+    y: i32,
+    frame: &mut [u8],
+    frame_width: u32,
+    frame_height: u32,
+    flip_y: bool,
+) {
+    blit_sprite_with(
+        sprite_grid,
+        sprite_pos_x,
+        sprite_pos_y,
+        x,
+        y,
+        frame,
+        frame_width,
+        frame_height,
+        flip_y,
+        dim,
+    );
+}
+
+// mostly grey, at a bit under half brightness
+fn dim([r, g, b]: [u8; 3]) -> [u8; 3] {
+    let grey = (r as u32 * 3 + g as u32 * 6 + b as u32) / 10;
+    [r, g, b].map(|c| ((grey * 3 + c as u32) / 4 * 2 / 5) as u8)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn blit_sprite_with(
+    sprite_grid: &SpriteGrid<Rgb<u8>, Vec<u8>>,
+    sprite_pos_x: u32,
+    sprite_pos_y: u32,
+    x: i32,
+    y: i32,
+    frame: &mut [u8],
+    frame_width: u32,
+    frame_height: u32,
+    flip_y: bool,
+    color: impl Fn([u8; 3]) -> [u8; 3],
 ) {
     for (spritex, spritey, rgb) in sprite_grid.enumerate_pixels(sprite_pos_x, sprite_pos_y, flip_y)
     {
         let abs_y = y + spritey as i32;
         let abs_x = x + spritex as i32;
         if abs_x >= 0 && abs_x < frame_width as i32 && abs_y >= 0 && abs_y < frame_height as i32 {
-            let &image::Rgb([r, g, b]) = rgb;
+            let [r, g, b] = color(rgb.0);
             let s = [r, g, b, 0xff];
             let idx = (abs_y * (frame_width as i32) * 4 + abs_x * 4) as usize;
             frame[idx..idx + 4].copy_from_slice(&s);
         }
     }
 }
+
+/// Lays a tile sprite flat as an isometric diamond as wide as the sprite and
+/// half as high, with `x`, `y` the upper left of the diamond. A `height` above
+/// zero raises it into a block with the sprite on top and shaded on the two
+/// sides facing the viewer.
+#[allow(clippy::too_many_arguments)]
+pub fn blit_tile_iso(
+    sprite_grid: &SpriteGrid<Rgb<u8>, Vec<u8>>,
+    sprite_pos_x: u32,
+    sprite_pos_y: u32,
+    x: i32,
+    y: i32,
+    height: i32,
+    frame: &mut [u8],
+    frame_width: u32,
+    frame_height: u32,
+) {
+    blit_tile_iso_with(
+        sprite_grid,
+        sprite_pos_x,
+        sprite_pos_y,
+        x,
+        y,
+        height,
+        frame,
+        frame_width,
+        frame_height,
+        |rgb| rgb,
+    );
+}
+
+/// Like `blit_tile_iso`, dimmed the same way as `blit_sprite_dimmed`.
+#[allow(clippy::too_many_arguments)]
+pub fn blit_tile_iso_dimmed(
+    sprite_grid: &SpriteGrid<Rgb<u8>, Vec<u8>>,
+    sprite_pos_x: u32,
+    sprite_pos_y: u32,
+    x: i32,
+    y: i32,
+    height: i32,
+    frame: &mut [u8],
+    frame_width: u32,
+    frame_height: u32,
+) {
+    blit_tile_iso_with(
+        sprite_grid,
+        sprite_pos_x,
+        sprite_pos_y,
+        x,
+        y,
+        height,
+        frame,
+        frame_width,
+        frame_height,
+        dim,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn blit_tile_iso_with(
+    sprite_grid: &SpriteGrid<Rgb<u8>, Vec<u8>>,
+    sprite_pos_x: u32,
+    sprite_pos_y: u32,
+    x: i32,
+    y: i32,
+    height: i32,
+    frame: &mut [u8],
+    frame_width: u32,
+    frame_height: u32,
+    color: impl Fn([u8; 3]) -> [u8; 3],
+) {
+    // tiles are square, black is left out as in `blit_sprite`
+    let size = sprite_grid.cell_size().0 as usize;
+    let mut sprite = vec![None; size * size];
+    for (spritex, spritey, rgb) in sprite_grid.enumerate_pixels(sprite_pos_x, sprite_pos_y, false) {
+        sprite[spritey as usize * size + spritex as usize] = Some(rgb.0);
+    }
+    let sample = |u: f32, v: f32| {
+        let sx = ((u * size as f32) as usize).min(size - 1);
+        let sy = ((v * size as f32) as usize).min(size - 1);
+        sprite[sy * size + sx]
+    };
+
+    let width = size as i32;
+    let half = width / 2;
+    let mut put = |abs_x: i32, abs_y: i32, rgb: [u8; 3]| {
+        if abs_x >= 0 && abs_x < frame_width as i32 && abs_y >= 0 && abs_y < frame_height as i32 {
+            let [r, g, b] = color(rgb);
+            let s = [r, g, b, 0xff];
+            let idx = (abs_y * (frame_width as i32) * 4 + abs_x * 4) as usize;
+            frame[idx..idx + 4].copy_from_slice(&s);
+        }
+    };
+
+    for px in 0..width {
+        // offset from the top corner, to the middle of the pixel
+        let dx = px as f32 + 0.5 - half as f32;
+
+        for py in -height..half {
+            let dy = py as f32 + 0.5;
+
+            // top face, raised by `height`
+            let top = dy + height as f32;
+            let u = dx / width as f32 + top / half as f32;
+            let v = top / half as f32 - dx / width as f32;
+            if (0.0..1.0).contains(&u) && (0.0..1.0).contains(&v) {
+                if let Some(rgb) = sample(u, v) {
+                    put(x + px, y + py, rgb);
+                }
+                continue;
+            }
+
+            if height == 0 || v < 1.0 && u < 1.0 {
+                continue;
+            }
+
+            // sides below the lower edges, the south face on the left and
+            // the east face on the right, with the sprite's rows running up
+            let (along, edge, shade) = if px < half {
+                let along = (px as f32 + 0.5) / half as f32;
+                (along, half as f32 / 2.0 * (1.0 + along), 3)
+            } else {
+                let along = (px as f32 + 0.5 - half as f32) / half as f32;
+                (along, half as f32 / 2.0 * (2.0 - along), 4)
+            };
+            let up = edge - dy;
+            if up < 0.0 || up >= height as f32 {
+                continue;
+            }
+            let u = if px < half { along } else { 1.0 - along };
+            if let Some(rgb) = sample(u, 1.0 - up / height as f32) {
+                put(x + px, y + py, rgb.map(|c| (c as u32 * shade / 5) as u8));
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Levels the player has left, kept so they can come back to them.
+//!
+//! A level is stored when the player takes the stairs off it and taken out
+//! again when they return, with its map, how much of it was explored and
+//! whatever monsters and items were left on it. Stored levels are plain data,
+//! the entities are rebuilt on return.
+
+use std::collections::BTreeMap;
+
+use legion::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::*,
+    game::TheRng,
+    map::{Map, TileType},
+    monsters::{MonsterId, Monsters},
+    resources::{LevelStats, PlayerPosition},
+    simulation::Simulation,
+    spawn,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredEnemy {
+    pub monster_type: MonsterId,
+    pub pos: MapPosition,
+    pub health: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredItem {
+    pub item: EntityType,
+    pub pos: MapPosition,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Floor {
+    pub map: Map,
+    pub stats: LevelStats,
+    pub enemies: Vec<StoredEnemy>,
+    pub items: Vec<StoredItem>,
+}
+
+/// Every level the player has been on and isn't on right now, by level.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Dungeon {
+    floors: BTreeMap<i32, Floor>,
+}
+
+impl Dungeon {
+    pub fn store(&mut self, floor: Floor) {
+        self.floors.insert(floor.stats.level, floor);
+    }
+
+    pub fn take(&mut self, level: i32) -> Option<Floor> {
+        self.floors.remove(&level)
+    }
+
+    pub fn get(&self, level: i32) -> Option<&Floor> {
+        self.floors.get(&level)
+    }
+
+    pub fn len(&self) -> usize {
+        self.floors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.floors.is_empty()
+    }
+}
+
+/// Moves the current level, and the monsters and items on it, out of the
+/// world and into the `Dungeon`, and stops the player. A level left going down
+/// gets stairs down if it had none, so there's a way back to the levels below.
+pub fn leave_floor(game: &mut Simulation, going_down: bool) {
+    let mut map = game.resources.get::<Map>().unwrap().clone();
+    let stats = game.resources.get::<LevelStats>().unwrap().clone();
+
+    if going_down && !map.cells.contains(&TileType::Exit) {
+        let from = map
+            .cells
+            .iter()
+            .position(|&t| t == TileType::StairsUp)
+            .map(|idx| {
+                let (x, y) = map.rev_idx(idx);
+                MapPosition { x, y }
+            })
+            .unwrap_or(game.resources.get::<PlayerPosition>().unwrap().0);
+        if let Some(pos) = spawn::exit_spot(&map, from) {
+            let idx = map.idx(pos.x, pos.y);
+            map.set_tile(idx, TileType::Exit);
+        }
+    }
+
+    let mut query =
+        <(Entity, &EntityType, &MapPosition, &Health)>::query().filter(component::<EnemyFlag>());
+    let enemies = query
+        .iter(&game.world)
+        .filter_map(|(&entity, &entity_type, &pos, &Health(health))| {
+            let EntityType::Monster(monster_type) = entity_type else {
+                return None;
+            };
+            Some((
+                entity,
+                StoredEnemy {
+                    monster_type,
+                    pos,
+                    health,
+                },
+            ))
+        })
+        .collect::<Vec<_>>();
+
+    let mut query = <(Entity, &EntityType, &MapPosition)>::query();
+    let items = query
+        .iter(&game.world)
+        .filter(|&(_, et, _)| *et == EntityType::Ammo || *et == EntityType::Health)
+        .map(|(&entity, &item, &pos)| (entity, StoredItem { item, pos }))
+        .collect::<Vec<_>>();
+
+    for &(entity, _) in enemies.iter() {
+        game.world.remove(entity);
+    }
+    for &(entity, _) in items.iter() {
+        game.world.remove(entity);
+    }
+
+    // a step still under way would otherwise finish from wherever the player
+    // comes out on the next level
+    let mut player_entry = game.world.entry(game.entities.player).unwrap();
+    player_entry.remove_component::<Moving>();
+    if let Ok(action_state) = player_entry.get_component_mut::<ActionState>() {
+        action_state.0 = types::Action::Stationary;
+    }
+
+    game.resources.get_mut::<Dungeon>().unwrap().store(Floor {
+        map,
+        stats,
+        enemies: enemies.into_iter().map(|(_, e)| e).collect(),
+        items: items.into_iter().map(|(_, i)| i).collect(),
+    });
+}
+
+/// Puts a stored level's monsters and items back into the world and makes it
+/// the current level. Returns its map, not inserted yet, and where the player
+/// comes out: by the stairs down when coming up, else by the stairs up. Fails,
+/// leaving the world alone, when there's nowhere to come out.
+pub fn restore_floor(
+    game: &mut Simulation,
+    floor: Floor,
+    going_up: bool,
+) -> Result<(Map, MapPosition), spawn::MapFull> {
+    let Floor {
+        map,
+        stats,
+        enemies,
+        items,
+    } = floor;
+    let mut rng = game.resources.get_mut::<TheRng>().unwrap();
+    let monsters = game.resources.get::<Monsters>().unwrap();
+
+    let stairs = match going_up {
+        true => TileType::Exit,
+        false => TileType::StairsUp,
+    };
+    let occupied = enemies.iter().map(|e| e.pos).collect::<Vec<_>>();
+    let arrival = spawn::arrival_spot(&map, &mut rng, stairs, &occupied)?;
+
+    for StoredEnemy {
+        monster_type,
+        pos,
+        health,
+    } in enemies
+    {
+        let entity = spawn::push_enemy(
+            &mut game.world,
+            &mut rng,
+            &monsters,
+            stats.level,
+            pos,
+            Some(monster_type),
+        );
+        game.world
+            .entry(entity)
+            .unwrap()
+            .add_component(Health(health));
+    }
+
+    for StoredItem { item, pos } in items {
+        spawn::push_item(&mut game.world, item, pos);
+    }
+
+    drop(rng);
+    drop(monsters);
+    game.resources.insert(stats);
+
+    Ok((map, arrival))
+}
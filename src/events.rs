@@ -0,0 +1,80 @@
+use legion::Entity;
+
+use crate::components::{types::Facing, EntityType, MapPosition};
+use crate::monsters::MonsterId;
+
+/// Something that happened during a tick. Systems emit these instead of
+/// reaching into audio, score or other bookkeeping themselves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    EnemyKilled {
+        entity: Entity,
+        monster: MonsterId,
+        pos: MapPosition,
+    },
+    EnemyDamaged {
+        entity: Entity,
+        monster: MonsterId,
+        amount: i32,
+        health: i32,
+    },
+    PlayerDamaged {
+        amount: i32,
+        health: i32,
+    },
+    ProjectileHit {
+        target: Entity,
+        pos: MapPosition,
+    },
+    ItemPickedUp {
+        item: EntityType,
+        pos: MapPosition,
+    },
+    ShotFired {
+        pos: MapPosition,
+        facing: Facing,
+    },
+    WallBumped {
+        pos: MapPosition,
+    },
+    DoorOpened {
+        pos: MapPosition,
+    },
+    WallDestroyed {
+        pos: MapPosition,
+    },
+    /// every enemy on the level is dead, emitted once per level
+    LevelCleared {
+        level: i32,
+    },
+    ExitReached {
+        level: i32,
+    },
+    StairsUpReached {
+        level: i32,
+    },
+}
+
+/// Queue of the current tick's events, in the order they were emitted.
+///
+/// It's cleared at the start of every tick and handed to the subscriber
+/// systems in `ScheduleName::Events` at the end, so after `Simulation::step`
+/// it still holds what that tick did.
+#[derive(Default)]
+pub struct GameEvents {
+    events: Vec<GameEvent>,
+}
+
+impl GameEvents {
+    pub fn emit(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GameEvent> {
+        self.events.iter()
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
@@ -1,22 +1,27 @@
-use std::rc::Rc;
+use std::{path::Path, rc::Rc};
 
-use crate::components::*;
+use crate::animations::{Animations, Tags};
+use crate::aseprite::AsepriteSheet;
+use crate::audio::RodioAudio;
 use crate::data::*;
-
+use crate::input::{InputMap, TickInput};
+use crate::monsters::Monsters;
+use crate::render;
 use crate::resources::*;
-use crate::shapes::FrameBufferTarget;
+use crate::save;
+use crate::simulation::Simulation;
 use crate::spritegrid;
-use crate::ScheduleBag;
-use embedded_graphics::text::Alignment;
+use crate::tiled::LevelList;
+use image::RgbaImage;
 use legion::*;
 use pixels::{Pixels, SurfaceTexture};
-use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 use winit::window::Window;
 use winit_input_helper::WinitInputHelper;
 
-pub(crate) type TheRng = pcg_mwc::Mwc256XXA64;
+pub type TheRng = pcg_mwc::Mwc256XXA64;
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum GameState {
     Init,
     GenerateNewMap(i32),
@@ -37,35 +42,72 @@ pub type SpriteGrid = spritegrid::SpriteGrid<image::Rgb<u8>, Vec<u8>>;
 
 pub struct CoreGame {
     pub input: WinitInputHelper,
-    sprite_state: bool,
-
-    pub world: World,
-    pub resources: Resources,
 
-    pub schedule_bag: ScheduleBag,
+    pub sim: Simulation,
 
-    pub entities: EntityContainer,
+    pub pixels: Pixels,
 
     // need to hold on to this value so it doesn't drop
-    pub astream: rodio::OutputStream,
+    pub astream: Option<rodio::OutputStream>,
 }
 
-pub async fn init(window: Rc<Window>) -> CoreGame {
-    use image::ImageReader;
+/// Starts a new run from `seed`, or a random one if there's none or it's invalid,
+/// with the data files in the working directory.
+pub fn new_simulation(seed: Option<String>) -> Simulation {
+    let seed = seed.unwrap_or_else(|| rand::random::<u64>().to_string());
+    let (seed, mut sim) = match Simulation::from_seed(&seed) {
+        Ok(sim) => (seed, sim),
+        Err(e) => {
+            log::error!("invalid seed {:?}, using a random one: {}", seed, e);
+            let seed = rand::random::<u64>().to_string();
+            let sim = Simulation::from_seed(&seed).unwrap();
+            (seed, sim)
+        }
+    };
+    load_data(&mut sim);
 
-    let (astream, astream_handle) = rodio::OutputStream::try_default().unwrap();
-    let hit_sounds = create_sound_map();
+    let json = serde_json::to_string(&*sim.resources.get::<TheRng>().unwrap()).unwrap();
+    log::info!("RNG Seed: {} {}", seed, json);
 
-    let img = ImageReader::new(std::io::Cursor::new(SPRITES))
-        .with_guessed_format()
-        .unwrap()
-        .decode()
-        .unwrap()
-        .to_rgb8();
-    let sprite_grid = SpriteGrid::new(img, 16, 16, 32, 32 * 3);
+    sim
+}
 
-    let shapes = FrameBufferTarget::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+/// Picks up the game saved by `save_on_exit`, if any. The save is removed once
+/// loaded so a run can only be resumed once.
+pub fn resume_saved_game() -> Option<Simulation> {
+    if !Path::new(SAVE_FILE).exists() {
+        return None;
+    }
+
+    match save::load_from_file(SAVE_FILE) {
+        Ok(mut sim) => {
+            log::info!("resumed game from {}", SAVE_FILE);
+            load_data(&mut sim);
+            if let Err(e) = std::fs::remove_file(SAVE_FILE) {
+                log::warn!("failed to remove {}: {}", SAVE_FILE, e);
+            }
+            Some(sim)
+        }
+        Err(e) => {
+            log::error!("failed to load {}: {}", SAVE_FILE, e);
+            None
+        }
+    }
+}
+
+/// Saves a game that's in progress so the next start resumes it.
+pub fn save_on_exit(game: &CoreGame) {
+    if game.sim.game_state() != GameState::Running {
+        return;
+    }
+
+    match save::save_to_file(&game.sim, SAVE_FILE) {
+        Ok(()) => log::info!("saved game to {}", SAVE_FILE),
+        Err(e) => log::error!("failed to save game to {}: {}", SAVE_FILE, e),
+    }
+}
 
+pub async fn init(window: Rc<Window>, mut sim: Simulation) -> CoreGame {
     let pixels = {
         let window_size = window.inner_size();
         let surface_texture =
@@ -77,135 +119,178 @@ pub async fn init(window: Rc<Window>) -> CoreGame {
 
     let input = WinitInputHelper::new();
 
-    let sprite_state = true;
-
-    let rng = TheRng::from_entropy();
-    let json = serde_json::to_string(&rng).unwrap();
-    log::info!("RNG Seed: {}", json);
-
-    let mut world = World::default();
-    let mut resources = Resources::default();
-
-    let player_entity = world.push((
-        PlayerFlag,
-        AnimationState::default(),
-        RenderableSprite::default(),
-        ActionState(types::Action::Stationary),
-        MapPosition { x: 1, y: 1 },
-        Viewshed {
-            visible: Vec::new(),
-            range: 4,
-        },
-        Health(10),
-        Score(0),
-    ));
-
-    world
-        .entry(player_entity)
-        .unwrap()
-        .add_component(EntityType::Player);
-
-    world.entry(player_entity).unwrap().add_component(Ammo(10));
-
-    world.push((PlayerViewportFlag,));
-    world.push((UpdateViewshedsFlag,));
-
-    let level_info_entity = world.push((
-        TextBlock {
-            text: "Level: 1".to_owned(),
-            color: (0, 210, 0),
-            alignment: Alignment::Left,
-        },
-        FixedScreenPos { x: 5, y: 5 },
-    ));
-
-    let score_info_entity = world.push((
-        TextBlock {
-            text: "Score: 0".to_owned(),
-            color: (0, 210, 0),
-            alignment: Alignment::Left,
-        },
-        FixedScreenPos { x: 5, y: 5 + 8 },
-    ));
-
-    let remaining_info_entity = world.push((
-        TextBlock {
-            text: "Remaining: ??".to_owned(),
-            color: (0, 210, 0),
-            alignment: Alignment::Left,
-        },
-        FixedScreenPos { x: 5 + 100, y: 5 },
-    ));
-
-    let health_info_entity = world.push((
-        TextBlock {
-            text: "Health:".to_owned(),
-            color: (0, 210, 0),
-            alignment: Alignment::Left,
-        },
-        FixedScreenPos {
-            x: 5 + 100 + 100,
-            y: 5,
-        },
-    ));
-
-    let ammo_info_entity = world.push((
-        TextBlock {
-            text: "Ammo:".to_owned(),
-            color: (0, 210, 0),
-            alignment: Alignment::Left,
-        },
-        FixedScreenPos {
-            x: 5 + 100 + 100,
-            y: 5 + 8,
-        },
-    ));
-
-    let entities = EntityContainer {
-        player: player_entity,
-        level: level_info_entity,
-        health: health_info_entity,
-        ammo: ammo_info_entity,
-        remaining: remaining_info_entity,
-        score: score_info_entity,
+    // fall back to the silent default if there's no usable output device
+    let astream = match RodioAudio::try_default() {
+        Ok((audio, astream)) => {
+            sim.resources.insert(AudioHandler::new(audio));
+            Some(astream)
+        }
+        Err(e) => {
+            log::warn!("no audio output, running silent: {}", e);
+            None
+        }
     };
 
-    let level_stats = LevelStats {
-        level: 1,
-        ..Default::default()
-    };
-
-    let audio = AudioHandler {
-        astream_handle,
-        hit_sounds,
-    };
-
-    resources.insert(PlayerEntity(player_entity));
-    resources.insert(PlayerPosition::default());
-    resources.insert(shapes);
-    resources.insert(pixels);
-    resources.insert(sprite_grid);
-    resources.insert(level_stats);
-    resources.insert(rng);
-    resources.insert(GameState::Init);
-    resources.insert(MobPositions::default());
-    resources.insert(audio);
+    render::insert_render_resources(&mut sim.resources);
+    sim.resources.insert(load_input_map());
 
     CoreGame {
         input,
-        sprite_state,
-        world,
-        resources,
-        entities,
-        schedule_bag: ScheduleBag::default(),
+        sim,
+        pixels,
         astream,
     }
 }
 
+/// Replaces the built-in monsters, levels, sprites and animations with the
+/// files in the working directory, see `data_files`.
+pub fn load_data(sim: &mut Simulation) {
+    let tags = match load_sprite_sheet() {
+        Some(AsepriteSheet { grid, tags }) => {
+            sim.resources.insert(grid);
+            tags
+        }
+        None => Tags::new(),
+    };
+    let monsters = load_monsters();
+    sim.resources.insert(load_level_list(&monsters));
+    sim.resources.insert(load_animations(&monsters, &tags));
+    sim.resources.insert(monsters);
+}
+
+/// The data files `load_data` finds in the working directory, each with a
+/// hash of its contents. Maps and images they name aren't included.
+pub fn data_files() -> Vec<(String, u64)> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        [
+            MONSTERS_FILE,
+            LEVELS_FILE,
+            SPRITE_SHEET_FILE,
+            ANIMATIONS_FILE,
+        ]
+        .into_iter()
+        .filter_map(|path| {
+            let bytes = std::fs::read(path).ok()?;
+            Some((path.to_owned(), data_hash(&bytes)))
+        })
+        .collect()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    Vec::new()
+}
+
+// FNV-1a, which unlike std's hasher stays the same between Rust releases
+fn data_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn load_input_map() -> InputMap {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(json) = std::fs::read_to_string(BINDINGS_FILE) {
+        match InputMap::from_json(&json) {
+            Ok(input_map) => return input_map,
+            Err(e) => log::error!("invalid {}, using default bindings: {}", BINDINGS_FILE, e),
+        }
+    }
+
+    InputMap::default()
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+fn load_level_list(monsters: &Monsters) -> LevelList {
+    #[cfg(not(target_arch = "wasm32"))]
+    if Path::new(LEVELS_FILE).exists() {
+        match LevelList::load(LEVELS_FILE, monsters) {
+            Ok(levels) => {
+                log::info!("loaded {} authored levels", levels.len());
+                return levels;
+            }
+            Err(e) => log::error!("invalid {}, generating every level: {}", LEVELS_FILE, e),
+        }
+    }
+
+    LevelList::default()
+}
+
+fn load_monsters() -> Monsters {
+    #[cfg(not(target_arch = "wasm32"))]
+    if Path::new(MONSTERS_FILE).exists() {
+        match Monsters::load(MONSTERS_FILE) {
+            Ok(monsters) => {
+                log::info!("loaded {} monsters", monsters.iter().count());
+                return monsters;
+            }
+            Err(e) => log::error!(
+                "invalid {}, using the built-in monsters: {}",
+                MONSTERS_FILE,
+                e
+            ),
+        }
+    }
+
+    Monsters::default()
+}
+
+fn load_sprite_sheet() -> Option<AsepriteSheet> {
+    #[cfg(not(target_arch = "wasm32"))]
+    if Path::new(SPRITE_SHEET_FILE).exists() {
+        match AsepriteSheet::load(SPRITE_SHEET_FILE) {
+            Ok(sheet) => {
+                log::info!("loaded sprite sheet with {} tags", sheet.tags.len());
+                return Some(sheet);
+            }
+            Err(e) => log::error!(
+                "invalid {}, using the built-in sprites: {}",
+                SPRITE_SHEET_FILE,
+                e
+            ),
+        }
+    }
+
+    None
+}
+
+// the monsters' own frames fill in for clips missing from either list
+fn load_animations(monsters: &Monsters, tags: &Tags) -> Animations {
+    #[cfg(not(target_arch = "wasm32"))]
+    if Path::new(ANIMATIONS_FILE).exists() {
+        match Animations::load(ANIMATIONS_FILE, tags) {
+            Ok(animations) => return animations.with_monsters(monsters),
+            Err(e) => log::error!(
+                "invalid {}, using the built-in animations: {}",
+                ANIMATIONS_FILE,
+                e
+            ),
+        }
+    }
+
+    Animations::from_json(ANIMATIONS)
+        .expect("built-in animations.json is invalid")
+        .with_monsters(monsters)
+}
+
+/// Translates the current winit key state into actions using the `InputMap`.
+pub fn tick_input(game: &CoreGame) -> TickInput {
+    let input_map = game.sim.resources.get::<InputMap>().unwrap();
+    input_map.tick_input(&game.input)
+}
+
+/// Renders the current frame on the CPU and presents it on the window surface.
+/// `alpha` is how far into the next tick the frame is drawn, see `TickAlpha`.
+pub fn present(game: &mut CoreGame, alpha: f32) -> Result<(), pixels::Error> {
+    game.sim.resources.insert(TickAlpha(alpha));
+    render::do_render(&mut game.sim);
+
+    let frame = game.sim.resources.get::<RgbaImage>().unwrap();
+    game.pixels.frame_mut().copy_from_slice(&frame);
+
+    game.pixels.render()
+}
+
 pub fn play_sound(sound: &str, audio: &AudioHandler) {
-    let hit_sound = audio.hit_sounds.get(sound).unwrap().clone();
-    let file = std::io::Cursor::new(hit_sound);
-    let sink = audio.astream_handle.play_once(file).unwrap();
-    sink.set_volume(0.8);
-    sink.detach();
+    audio.backend.play(audio.tick, sound, 0.8);
 }
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use winit::event::VirtualKeyCode;
+use winit_input_helper::WinitInputHelper;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GameAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    FireUp,
+    FireDown,
+    FireLeft,
+    FireRight,
+    Fire,
+    Confirm,
+    Quit,
+}
+
+impl GameAction {
+    pub const ALL: [GameAction; 11] = [
+        GameAction::MoveUp,
+        GameAction::MoveDown,
+        GameAction::MoveLeft,
+        GameAction::MoveRight,
+        GameAction::FireUp,
+        GameAction::FireDown,
+        GameAction::FireLeft,
+        GameAction::FireRight,
+        GameAction::Fire,
+        GameAction::Confirm,
+        GameAction::Quit,
+    ];
+}
+
+/// Bit set of `GameAction`s.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ActionSet(pub u16);
+
+impl ActionSet {
+    pub fn insert(&mut self, action: GameAction) {
+        self.0 |= 1 << action as u16;
+    }
+
+    pub fn contains(&self, action: GameAction) -> bool {
+        self.0 & (1 << action as u16) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+/// Player input for a single simulation tick, independent of any windowing
+/// backend. `held` is every action whose binding is down, `pressed` only the
+/// ones that went down this tick.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TickInput {
+    pub held: ActionSet,
+    pub pressed: ActionSet,
+}
+
+impl TickInput {
+    pub fn held(&self, action: GameAction) -> bool {
+        self.held.contains(action)
+    }
+
+    pub fn pressed(&self, action: GameAction) -> bool {
+        self.pressed.contains(action)
+    }
+
+    pub fn hold(mut self, action: GameAction) -> Self {
+        self.held.insert(action);
+        self
+    }
+
+    pub fn press(mut self, action: GameAction) -> Self {
+        self.held.insert(action);
+        self.pressed.insert(action);
+        self
+    }
+}
+
+/// Key bindings for each action, translating raw winit key state into a
+/// `TickInput`. Loaded from a JSON object of action name to key names, e.g.
+/// `{ "MoveUp": ["W", "Up"], "Quit": ["Escape"] }`. Actions missing from the
+/// file keep their default bindings.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputMap {
+    pub bindings: HashMap<GameAction, Vec<VirtualKeyCode>>,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use GameAction::*;
+        use VirtualKeyCode as Key;
+
+        let bindings = HashMap::from([
+            (MoveUp, vec![Key::W]),
+            (MoveDown, vec![Key::S]),
+            (MoveLeft, vec![Key::A]),
+            (MoveRight, vec![Key::D]),
+            (FireUp, vec![Key::Up]),
+            (FireDown, vec![Key::Down]),
+            (FireLeft, vec![Key::Left]),
+            (FireRight, vec![Key::Right]),
+            (Fire, vec![Key::Space]),
+            (Confirm, vec![Key::Space]),
+            (Quit, vec![Key::Escape]),
+        ]);
+
+        InputMap { bindings }
+    }
+}
+
+impl InputMap {
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let overrides: InputMap = serde_json::from_str(json)?;
+
+        let mut map = InputMap::default();
+        map.bindings.extend(overrides.bindings);
+
+        Ok(map)
+    }
+
+    pub fn tick_input(&self, input: &WinitInputHelper) -> TickInput {
+        let mut tick_input = TickInput::default();
+
+        for (&action, keys) in self.bindings.iter() {
+            if keys.iter().any(|&k| input.key_held(k)) {
+                tick_input.held.insert(action);
+            }
+            if keys.iter().any(|&k| input.key_pressed(k)) {
+                tick_input.pressed.insert(action);
+            }
+        }
+
+        tick_input
+    }
+}
+
+/// Gathers input between simulation ticks. A press lands in exactly one tick,
+/// even when a frame runs no ticks at all or several of them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InputLatch {
+    held: ActionSet,
+    pressed: ActionSet,
+}
+
+impl InputLatch {
+    pub fn latch(&mut self, input: TickInput) {
+        self.held = input.held;
+        self.pressed.0 |= input.pressed.0;
+    }
+
+    /// The input for the next tick. Pending presses are handed out once.
+    pub fn take(&mut self) -> TickInput {
+        let pressed = std::mem::take(&mut self.pressed);
+
+        TickInput {
+            held: ActionSet(self.held.0 | pressed.0),
+            pressed,
+        }
+    }
+}
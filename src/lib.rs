@@ -0,0 +1,33 @@
+pub mod animations;
+pub mod aseprite;
+pub mod audio;
+pub mod components;
+pub mod data;
+pub mod draw;
+pub mod dungeon;
+pub mod events;
+pub mod game;
+pub mod input;
+pub mod map;
+pub mod mapgen;
+pub mod monsters;
+pub mod pixel_helper;
+pub mod prefab;
+pub mod render;
+pub mod replay;
+pub mod resources;
+pub mod rng;
+pub mod save;
+pub mod shapes;
+pub mod simulation;
+pub mod spawn;
+pub mod spritegrid;
+pub mod sprites;
+pub mod systems;
+pub mod tiled;
+pub mod timestep;
+pub mod update;
+pub mod utils;
+
+use data::*;
+use systems::*;
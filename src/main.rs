@@ -1,29 +1,18 @@
-use std::rc::Rc;
-
-use log::error;
-use update::UpdateResult;
+use std::{panic::AssertUnwindSafe, rc::Rc};
+
+use centsyl::{
+    data::*,
+    game,
+    input::InputLatch,
+    map::Projection,
+    pixel_helper,
+    replay::Replay,
+    timestep::{FixedTimestep, Instant},
+    update::UpdateResult,
+};
+use log::{error, info};
 use winit::{event::Event, event_loop::EventLoop};
 
-mod components;
-mod data;
-mod draw;
-mod game;
-mod map;
-mod pixel_helper;
-mod render;
-mod resources;
-mod rng;
-mod shapes;
-mod spawn;
-mod spritegrid;
-mod sprites;
-mod systems;
-mod update;
-mod utils;
-
-use data::*;
-use systems::*;
-
 fn main() {
     #[cfg(target_arch = "wasm32")]
     {
@@ -41,12 +30,47 @@ fn main() {
     }
 }
 
+// `--name <value>` on the command line, `?name=value` in the browser
+fn arg(name: &str) -> Option<String> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        let search = web_sys::window()?.location().search().ok()?;
+        web_sys::UrlSearchParams::new_with_str(&search)
+            .ok()?
+            .get(name)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let flag = format!("--{}", name);
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == flag {
+                return args.next();
+            }
+            if let Some(value) = arg.strip_prefix(&flag).and_then(|a| a.strip_prefix('=')) {
+                return Some(value.to_owned());
+            }
+        }
+        None
+    }
+}
+
+fn save_recording(recording: &Option<(String, Replay)>) {
+    if let Some((path, replay)) = recording {
+        match replay.save(path) {
+            Ok(()) => info!("saved replay of {} ticks to {}", replay.len(), path),
+            Err(e) => error!("failed to save replay to {}: {}", path, e),
+        }
+    }
+}
+
 async fn run() {
     // wasm example
     // https://github.com/parasyte/pixels/blob/main/examples/minimal-web/src/main.rs
     let event_loop = EventLoop::new();
 
-    let (window, p_width, p_height, mut _hidpi_factor) = pixel_helper::create_window(
+    let (window, _p_width, _p_height, mut _hidpi_factor) = pixel_helper::create_window(
         SCREEN_WIDTH as f64,
         SCREEN_HEIGHT as f64,
         "Zombie Hunter",
@@ -98,14 +122,58 @@ async fn run() {
         closure.forget();
     }
 
-    let mut game = game::init(Rc::clone(&window)).await;
+    // `--replay <file>` plays back a recording, `--record <file>` writes one
+    let playback = arg("replay").and_then(|path| match Replay::load(&path) {
+        Ok(replay) => Some(replay),
+        Err(e) => {
+            error!("failed to load replay {}: {}", path, e);
+            None
+        }
+    });
+    let sim = match (&playback, arg("seed")) {
+        (Some(replay), _) => replay.start().unwrap_or_else(|e| {
+            error!("replay has an invalid seed {:?}: {}", replay.seed, e);
+            game::new_simulation(None)
+        }),
+        (None, Some(seed)) => game::new_simulation(Some(seed)),
+        // pick up where the last session quit, unless recording from the start
+        (None, None) if arg("record").is_none() => {
+            game::resume_saved_game().unwrap_or_else(|| game::new_simulation(None))
+        }
+        (None, None) => game::new_simulation(None),
+    };
+
+    let mut game = game::init(Rc::clone(&window), sim).await;
+
+    // `--projection isometric` draws the map as diamonds instead of squares
+    let projection = match arg("projection").as_deref() {
+        None | Some("top-down") => Projection::TopDown,
+        Some("isometric") => Projection::Isometric,
+        Some(other) => {
+            error!("unknown projection {}, drawing top-down", other);
+            Projection::TopDown
+        }
+    };
+    game.sim.resources.insert(projection);
+
+    let mut playback_inputs = playback
+        .map(|replay| replay.inputs().collect::<Vec<_>>())
+        .unwrap_or_default()
+        .into_iter();
+    let mut recording = arg("record").map(|path| (path, Replay::for_simulation(&game.sim)));
+
+    // `--interpolate off` draws moving entities where the last tick left them
+    let interpolate = arg("interpolate").as_deref() != Some("off");
+    let mut timestep = FixedTimestep::new(TICKS_PER_SECOND);
+    let mut latch = InputLatch::default();
 
     event_loop.run(move |event, _, control_flow| {
         control_flow.set_poll();
 
         // render
         if let Event::RedrawRequested(_) = event {
-            let r = render::do_render(&mut game);
+            let alpha = if interpolate { timestep.alpha() } else { 0.0 };
+            let r = game::present(&mut game, alpha);
 
             if let Err(e) = r {
                 error!("pixels.render() failed: {}", e);
@@ -118,14 +186,48 @@ async fn run() {
         if game.input.update(&event) {
             use UpdateResult::*;
 
-            let r = update::do_update(&mut game);
+            if game.input.close_requested() || game.input.destroyed() {
+                game::save_on_exit(&game);
+                save_recording(&recording);
+                control_flow.set_exit();
+                return;
+            }
 
-            match r {
-                Ok(Exit) => control_flow.set_exit(),
-                Ok(None) => (),
-                Err(e) => {
+            // handle resize
+            if let Some(size) = game.input.window_resized() {
+                if let Err(e) = game.pixels.resize_surface(size.width, size.height) {
                     error!("{}", e);
                     control_flow.set_exit();
+                    return;
+                }
+            }
+
+            latch.latch(game::tick_input(&game));
+            timestep.update(Instant::now());
+
+            while timestep.tick() {
+                // recorded input first, then back to the keyboard
+                let live = latch.take();
+                let input = playback_inputs.next().unwrap_or(live);
+
+                if let Some((_, replay)) = &mut recording {
+                    replay.record(input);
+                }
+
+                // keep the recording of runs that crash, those are the interesting ones
+                let r = std::panic::catch_unwind(AssertUnwindSafe(|| game.sim.step(&input)));
+                match r {
+                    Ok(Exit) => {
+                        game::save_on_exit(&game);
+                        save_recording(&recording);
+                        control_flow.set_exit();
+                        return;
+                    }
+                    Ok(None) => (),
+                    Err(panic) => {
+                        save_recording(&recording);
+                        std::panic::resume_unwind(panic);
+                    }
                 }
             }
 
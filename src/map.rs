@@ -1,25 +1,200 @@
 use crate::components::*;
 
 use crate::game::TheRng;
+use crate::monsters::MonsterId;
 use crate::rng::Rng;
 use bracket_pathfinding::prelude::{a_star_search, Algorithm2D, BaseMap, Point, SmallVec};
+use serde::{Deserialize, Serialize};
 use types::RenderPosition;
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
+    DoorClosed,
+    DoorOpen,
+    /// shallow, slows down whoever wades through it
+    Water,
+    /// hurts whoever stands in it
+    Lava,
+    /// what's left of a cracked wall
+    Rubble,
+    /// wall that projectiles can knock down
+    CrackedWall,
+    /// stairs down to the next level
+    Exit,
+    /// stairs back up to the level before
+    StairsUp,
 }
 
+impl TileType {
+    pub fn blocks_movement(self) -> bool {
+        use TileType::*;
+        matches!(self, Wall | DoorClosed | CrackedWall)
+    }
+
+    pub fn blocks_visibility(self) -> bool {
+        use TileType::*;
+        matches!(self, Wall | DoorClosed | CrackedWall)
+    }
+
+    /// What the tile turns into when bumped into, closed doors open.
+    pub fn opened(self) -> Option<TileType> {
+        match self {
+            TileType::DoorClosed => Some(TileType::DoorOpen),
+            _ => None,
+        }
+    }
+
+    /// What the tile turns into when hit by a projectile.
+    pub fn destroyed(self) -> Option<TileType> {
+        match self {
+            TileType::CrackedWall => Some(TileType::Rubble),
+            _ => None,
+        }
+    }
+
+    /// How many times longer than usual it takes to step onto the tile.
+    pub fn move_ticks_factor(self) -> i32 {
+        match self {
+            TileType::Water => 2,
+            _ => 1,
+        }
+    }
+
+    /// Damage dealt every `HAZARD_DAMAGE_TICKS` to anything standing on the
+    /// tile.
+    pub fn damage(self) -> i32 {
+        match self {
+            TileType::Lava => 1,
+            _ => 0,
+        }
+    }
+
+    // cost of stepping onto the tile when pathing, hazards are avoided where
+    // there's a way around and doors take a bump to open first
+    fn path_cost(self) -> f32 {
+        use TileType::*;
+        match self {
+            Lava => 8.0,
+            DoorClosed => 2.0,
+            t => t.move_ticks_factor() as f32,
+        }
+    }
+}
+
+/// Rectangle of open floor laid out by a room based generator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Room {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Room {
+    pub fn contains(&self, pos: MapPosition) -> bool {
+        pos.x >= self.x
+            && pos.x < self.x + self.width
+            && pos.y >= self.y
+            && pos.y < self.y + self.height
+    }
+
+    pub fn overlaps(&self, other: &Room) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+
+    pub fn center(&self) -> MapPosition {
+        MapPosition {
+            x: self.x + self.width / 2,
+            y: self.y + self.height / 2,
+        }
+    }
+
+    pub fn random_spot(&self, rng: &mut TheRng) -> MapPosition {
+        MapPosition {
+            x: rng.range(self.x..self.x + self.width),
+            y: rng.range(self.y..self.y + self.height),
+        }
+    }
+}
+
+/// What a hand placed spawn marker asks for. `None` leaves the type to the
+/// usual roll.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpawnKind {
+    PlayerStart,
+    Enemy(Option<MonsterId>),
+    Item(Option<EntityType>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpawnPoint {
+    pub pos: MapPosition,
+    pub kind: SpawnKind,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
     pub width: i32,
     pub height: i32,
     pub cells: Vec<TileType>,
     pub blocks_movement: Vec<bool>,
     pub blocks_visibility: Vec<bool>,
+    /// Cells the player can see right now.
     pub visible_cells: Vec<bool>,
+    /// Cells the player has seen at some point, drawn from memory when out of
+    /// view.
+    pub revealed_cells: Vec<bool>,
+    // empty for maps that aren't made of rooms
+    #[serde(default)]
+    pub rooms: Vec<Room>,
+    /// Where prefabs were stamped. Kept apart from `rooms` so maps without
+    /// rooms don't start the player in one.
+    #[serde(default)]
+    pub prefab_areas: Vec<Room>,
+    /// Spawns placed by prefabs, used before any random placement.
+    #[serde(default)]
+    pub spawns: Vec<SpawnPoint>,
+    /// Open cells the player can walk to, in index order, closed doors left
+    /// out. Filled in by `keep_largest_region`.
+    #[serde(default)]
+    pub reachable: Vec<usize>,
 }
 
+/// How map positions are laid out on screen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Projection {
+    /// square tiles on a grid, the way the sprites are drawn
+    #[default]
+    TopDown,
+    /// diamond tiles half as high as they are wide, drawn back to front
+    Isometric,
+}
+
+impl Projection {
+    /// Screen offset for an offset of `x`, `y` pixels in top-down space, as
+    /// used by `Moving` and `ScreenDrawOffset`.
+    pub fn offset(self, x: f32, y: f32) -> (f32, f32) {
+        match self {
+            Projection::TopDown => (x, y),
+            Projection::Isometric => ((x - y) / 2.0, (x + y) / 4.0),
+        }
+    }
+
+    /// Inverse of `offset`.
+    pub fn unoffset(self, x: f32, y: f32) -> (f32, f32) {
+        match self {
+            Projection::TopDown => (x, y),
+            Projection::Isometric => (x + 2.0 * y, 2.0 * y - x),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct MapViewport {
     pub ulx: f32,
     pub uly: f32,
@@ -76,8 +251,9 @@ impl MapViewport {
         pos: MapPosition,
         mult: f32,
         screen_bounds: RenderPosition,
+        projection: Projection,
     ) -> Option<RenderPosition> {
-        let pos @ RenderPosition { x, y } = self.map_to_screen_pos(pos, mult);
+        let pos @ RenderPosition { x, y } = self.map_to_screen_pos(pos, mult, projection);
         if x < 0 || x >= screen_bounds.x || y < 0 || y > screen_bounds.y {
             None
         } else {
@@ -85,33 +261,76 @@ impl MapViewport {
         }
     }
 
-    pub fn map_to_screen_pos(&self, pos: MapPosition, mult: f32) -> RenderPosition {
-        let x = (pos.x as f32 - self.ulx) * mult;
-        let y = (pos.y as f32 - self.uly) * mult;
-        RenderPosition {
-            x: x as i32,
-            y: y as i32,
+    /// Upper left corner of the screen area covered by the tile at `pos`,
+    /// `mult` pixels wide. Isometric tiles are half as high as they are wide
+    /// and the middle of the viewport stays in the middle of the screen.
+    pub fn map_to_screen_pos(
+        &self,
+        pos: MapPosition,
+        mult: f32,
+        projection: Projection,
+    ) -> RenderPosition {
+        match projection {
+            Projection::TopDown => {
+                let x = (pos.x as f32 - self.ulx) * mult;
+                let y = (pos.y as f32 - self.uly) * mult;
+                RenderPosition {
+                    x: x as i32,
+                    y: y as i32,
+                }
+            }
+            Projection::Isometric => {
+                let (cx, cy) = self.center();
+                let (x, y) =
+                    projection.offset((pos.x as f32 - cx) * mult, (pos.y as f32 - cy) * mult);
+                RenderPosition {
+                    x: (x + (self.width - 1) as f32 * mult / 2.0).floor() as i32,
+                    y: (y + self.height as f32 * mult / 2.0).floor() as i32,
+                }
+            }
         }
     }
+
+    /// The map position under the screen pixel `x`, `y`, the inverse of
+    /// `map_to_screen_pos`. Can be outside the map.
+    pub fn screen_to_map_pos(
+        &self,
+        x: i32,
+        y: i32,
+        mult: f32,
+        projection: Projection,
+    ) -> MapPosition {
+        let (x, y) = match projection {
+            Projection::TopDown => (x as f32 / mult + self.ulx, y as f32 / mult + self.uly),
+            Projection::Isometric => {
+                let (cx, cy) = self.center();
+                let (x, y) = projection.unoffset(
+                    x as f32 + 0.5 - self.width as f32 * mult / 2.0,
+                    y as f32 + 0.5 - self.height as f32 * mult / 2.0,
+                );
+                (x / mult + cx, y / mult + cy)
+            }
+        };
+        MapPosition {
+            x: x.floor() as i32,
+            y: y.floor() as i32,
+        }
+    }
+
+    // map position shown in the middle of the screen
+    fn center(&self) -> (f32, f32) {
+        (
+            self.ulx + self.width as f32 / 2.0,
+            self.uly + self.height as f32 / 2.0,
+        )
+    }
 }
 
 impl Map {
     pub fn new(width: i32, height: i32, rng: &mut pcg_mwc::Mwc256XXA64) -> Map {
         use TileType::*;
 
-        let cells = vec![TileType::Wall; (width * height) as _];
-        let blocks_movement = vec![false; cells.len()];
-        let blocks_visibility = vec![false; cells.len()];
-        let visible_cells = vec![false; cells.len()];
-
-        let mut map = Map {
-            width,
-            height,
-            cells,
-            blocks_movement,
-            blocks_visibility,
-            visible_cells,
-        };
+        let mut map = Map::filled(width, height, Wall);
 
         for x in 0..width {
             for y in 0..height {
@@ -139,6 +358,33 @@ impl Map {
         map
     }
 
+    /// A map made up entirely of `tile`.
+    pub fn filled(width: i32, height: i32, tile: TileType) -> Map {
+        let cells = vec![tile; (width * height) as _];
+        let blocks_movement = vec![false; cells.len()];
+        let blocks_visibility = vec![false; cells.len()];
+        let visible_cells = vec![false; cells.len()];
+        let revealed_cells = vec![false; cells.len()];
+
+        Map {
+            width,
+            height,
+            cells,
+            blocks_movement,
+            blocks_visibility,
+            visible_cells,
+            revealed_cells,
+            rooms: Vec::new(),
+            prefab_areas: Vec::new(),
+            spawns: Vec::new(),
+            reachable: Vec::new(),
+        }
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && x < self.width && y >= 0 && y < self.height
+    }
+
     pub fn idx(&self, x: i32, y: i32) -> usize {
         (y * self.width + x) as usize
     }
@@ -149,37 +395,133 @@ impl Map {
 
     pub fn update_blocks_movement(&mut self) {
         self.cells.iter().enumerate().for_each(|(idx, typ)| {
-            self.blocks_movement[idx] = match *typ {
-                TileType::Wall => true,
-                TileType::Floor => false,
-            };
+            self.blocks_movement[idx] = typ.blocks_movement();
         });
     }
 
     pub fn update_blocks_visibility(&mut self) {
         self.cells.iter().enumerate().for_each(|(idx, typ)| {
-            self.blocks_visibility[idx] = match *typ {
-                TileType::Wall => true,
-                TileType::Floor => false,
-            };
+            self.blocks_visibility[idx] = typ.blocks_visibility();
         });
     }
 
-    pub fn random_open_spot(&self, rng: &mut TheRng) -> MapPosition {
-        // pick random x, y
-        let mut x;
-        let mut y;
-        loop {
-            x = rng.range(0..self.width);
-            y = rng.range(0..self.height);
-            let idx = self.idx(x, y);
+    /// Changes a single cell during play, keeping the blocking vectors in
+    /// step with it.
+    pub fn set_tile(&mut self, idx: usize, tile: TileType) {
+        self.cells[idx] = tile;
+        self.blocks_movement[idx] = tile.blocks_movement();
+        self.blocks_visibility[idx] = tile.blocks_visibility();
+    }
+
+    // open, or about to be once bumped into
+    fn passable(&self, idx: usize) -> bool {
+        !self.blocks_movement[idx] || self.cells[idx].opened().is_some()
+    }
+
+    /// Every cell reachable from `start` without crossing a cell that blocks
+    /// movement, `start` included. Closed doors count as open since bumping
+    /// into them opens them.
+    pub fn flood_fill(&self, start: usize) -> Vec<usize> {
+        let mut seen = vec![false; self.cells.len()];
+        let mut region = Vec::new();
+        let mut stack = vec![start];
+        seen[start] = true;
 
-            if !self.blocks_movement[idx] {
-                break;
+        while let Some(idx) = stack.pop() {
+            region.push(idx);
+            for (next, _) in self.get_available_exits(idx) {
+                if !seen[next] {
+                    seen[next] = true;
+                    stack.push(next);
+                }
+            }
+        }
+
+        region
+    }
+
+    /// Steps it takes to walk from `start` to every cell, `None` for the ones
+    /// that can't be reached.
+    pub fn walking_distances(&self, start: usize) -> Vec<Option<u32>> {
+        let mut distances = vec![None; self.cells.len()];
+        let mut queue = std::collections::VecDeque::from([start]);
+        distances[start] = Some(0);
+
+        while let Some(idx) = queue.pop_front() {
+            let steps = distances[idx].unwrap() + 1;
+            for (next, _) in self.get_available_exits(idx) {
+                if distances[next].is_none() {
+                    distances[next] = Some(steps);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// The connected open areas of the map, largest first.
+    pub fn regions(&self) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.cells.len()];
+        let mut regions = Vec::new();
+
+        for idx in 0..self.cells.len() {
+            if seen[idx] || !self.passable(idx) {
+                continue;
+            }
+
+            let region = self.flood_fill(idx);
+            for &i in region.iter() {
+                seen[i] = true;
             }
+            regions.push(region);
         }
 
-        MapPosition { x, y }
+        regions.sort_by_key(|r| std::cmp::Reverse(r.len()));
+        regions
+    }
+
+    /// Walls off every open pocket but the largest one and records what's
+    /// left in `reachable`, so anything placed on it can get to anything else.
+    /// Expects `blocks_movement` to be up to date and leaves both blocking
+    /// vectors updated.
+    pub fn keep_largest_region(&mut self) {
+        let mut regions = self.regions().into_iter();
+        let mut largest = regions.next().unwrap_or_default();
+
+        for region in regions {
+            for idx in region {
+                self.cells[idx] = TileType::Wall;
+            }
+        }
+        self.update_blocks_movement();
+        self.update_blocks_visibility();
+
+        // closed doors join regions up but can't be stood on
+        largest.retain(|&idx| !self.blocks_movement[idx]);
+        largest.sort_unstable();
+        self.reachable = largest;
+    }
+
+    pub fn is_reachable(&self, pos: MapPosition) -> bool {
+        self.in_bounds(pos.x, pos.y)
+            && self
+                .reachable
+                .binary_search(&self.idx(pos.x, pos.y))
+                .is_ok()
+    }
+
+    /// The room `pos` is in, if any.
+    pub fn room_at(&self, pos: MapPosition) -> Option<&Room> {
+        self.rooms.iter().find(|r| r.contains(pos))
+    }
+
+    /// The room or prefab area `pos` is in, if any.
+    pub fn area_at(&self, pos: MapPosition) -> Option<&Room> {
+        self.rooms
+            .iter()
+            .chain(self.prefab_areas.iter())
+            .find(|r| r.contains(pos))
     }
 
     pub fn open_path_a_b(&self, start: usize, end: usize) -> bool {
@@ -202,17 +544,12 @@ impl BaseMap for Map {
     fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
         let (x, y) = self.rev_idx(idx);
         let mut v = SmallVec::new();
-        if x > 0 && !self.blocks_movement[self.idx(x - 1, y)] {
-            v.push((self.idx(x - 1, y), 1.0));
-        }
-        if x + 1 < self.width && !self.blocks_movement[self.idx(x + 1, y)] {
-            v.push((self.idx(x + 1, y), 1.0));
-        }
-        if y > 0 && !self.blocks_movement[self.idx(x, y - 1)] {
-            v.push((self.idx(x, y - 1), 1.0));
-        }
-        if y + 1 < self.height && !self.blocks_movement[self.idx(x, y + 1)] {
-            v.push((self.idx(x, y + 1), 1.0));
+        for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+            let (nx, ny) = (x + dx, y + dy);
+            if self.in_bounds(nx, ny) && self.passable(self.idx(nx, ny)) {
+                let next = self.idx(nx, ny);
+                v.push((next, self.cells[next].path_cost()));
+            }
         }
         v
     }
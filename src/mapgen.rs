@@ -0,0 +1,455 @@
+use crate::components::MapPosition;
+use crate::game::TheRng;
+use crate::map::{Map, Room, TileType};
+use crate::prefab::{Prefab, PrefabTile};
+use crate::resources::Objective;
+use crate::rng::{CoinFlip, Rng};
+
+/// Builds the layout of a level. Generators only lay out tiles, the caller
+/// updates the movement and visibility blocking afterwards.
+pub trait MapGenerator: Send + Sync {
+    fn generate(&self, width: i32, height: i32, level: i32, rng: &mut TheRng) -> Map;
+}
+
+/// Walls on roughly one tile in ten inside a solid border, the original
+/// layout.
+pub struct ScatteredPillars;
+
+impl MapGenerator for ScatteredPillars {
+    fn generate(&self, width: i32, height: i32, _level: i32, rng: &mut TheRng) -> Map {
+        Map::new(width, height, rng)
+    }
+}
+
+/// Cellular automata caves: random noise smoothed into blobs, of which only
+/// the largest connected open area is kept.
+pub struct Caves {
+    /// chance in percent of a tile starting out as wall
+    pub fill_percent: u8,
+    pub smoothing_passes: u32,
+    /// least share of the interior, in percent, the kept cave has to cover.
+    /// Smaller results are regenerated a few times before settling for the
+    /// biggest one.
+    pub min_open_percent: usize,
+}
+
+impl Default for Caves {
+    fn default() -> Self {
+        Caves {
+            fill_percent: 45,
+            smoothing_passes: 4,
+            min_open_percent: 40,
+        }
+    }
+}
+
+impl Caves {
+    const ATTEMPTS: usize = 10;
+
+    fn attempt(&self, width: i32, height: i32, rng: &mut TheRng) -> Map {
+        use TileType::*;
+
+        let mut map = Map::filled(width, height, Wall);
+
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                let idx = map.idx(x, y);
+                if rng.d100() > self.fill_percent {
+                    map.cells[idx] = Floor;
+                }
+            }
+        }
+
+        for _ in 0..self.smoothing_passes {
+            let mut cells = map.cells.clone();
+            for x in 1..width - 1 {
+                for y in 1..height - 1 {
+                    let walls = wall_neighbours(&map, x, y);
+                    let idx = map.idx(x, y);
+                    if walls > 4 {
+                        cells[idx] = Wall;
+                    } else if walls < 4 {
+                        cells[idx] = Floor;
+                    }
+                }
+            }
+            map.cells = cells;
+        }
+
+        // fill in everything but the largest cave
+        map.update_blocks_movement();
+        map.keep_largest_region();
+
+        map
+    }
+}
+
+impl MapGenerator for Caves {
+    fn generate(&self, width: i32, height: i32, _level: i32, rng: &mut TheRng) -> Map {
+        let interior = ((width - 2) * (height - 2)).max(0) as usize;
+        let wanted = interior * self.min_open_percent / 100;
+
+        let mut best: Option<(usize, Map)> = None;
+        for _ in 0..Caves::ATTEMPTS {
+            let map = self.attempt(width, height, rng);
+            let open = map.reachable.len();
+            if open >= wanted {
+                return map;
+            }
+            if best.as_ref().is_none_or(|&(o, _)| open > o) {
+                best = Some((open, map));
+            }
+        }
+
+        best.unwrap().1
+    }
+}
+
+// walls among the 8 surrounding tiles, anything off the map counts as wall
+fn wall_neighbours(map: &Map, x: i32, y: i32) -> usize {
+    let mut walls = 0;
+    for dx in -1..=1 {
+        for dy in -1..=1 {
+            if dx == 0 && dy == 0 {
+                continue;
+            }
+            let (nx, ny) = (x + dx, y + dy);
+            if !map.in_bounds(nx, ny) || map.cells[map.idx(nx, ny)] == TileType::Wall {
+                walls += 1;
+            }
+        }
+    }
+    walls
+}
+
+/// Rooms and corridors. The interior is split in two again and again, binary
+/// space partition style, each leaf gets a room and the rooms of every pair of
+/// siblings are joined by an L shaped corridor. The rooms end up in
+/// `Map::rooms`.
+pub struct Rooms {
+    /// smallest side of a room, leaves are one bigger for the wall between
+    pub min_room: i32,
+    pub max_depth: u32,
+}
+
+impl Default for Rooms {
+    fn default() -> Self {
+        Rooms {
+            min_room: 3,
+            max_depth: 4,
+        }
+    }
+}
+
+impl Rooms {
+    // splits `leaf` and returns the rooms carved inside it
+    fn split(&self, map: &mut Map, leaf: Room, depth: u32, rng: &mut TheRng) -> Vec<Room> {
+        let min_leaf = self.min_room + 1;
+        let can_split_x = leaf.width >= 2 * min_leaf;
+        let can_split_y = leaf.height >= 2 * min_leaf;
+
+        let split_x = match (can_split_x, can_split_y) {
+            _ if depth >= self.max_depth => None,
+            (false, false) => None,
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (true, true) if leaf.width > leaf.height * 3 / 2 => Some(true),
+            (true, true) if leaf.height > leaf.width * 3 / 2 => Some(false),
+            (true, true) => Some(rng.coin_flip() == CoinFlip::Heads),
+        };
+
+        let Some(split_x) = split_x else {
+            let room = self.carve_room(map, leaf, rng);
+            return vec![room];
+        };
+
+        let (a, b) = if split_x {
+            let w = rng.range(min_leaf..leaf.width - min_leaf + 1);
+            (
+                Room { width: w, ..leaf },
+                Room {
+                    x: leaf.x + w,
+                    width: leaf.width - w,
+                    ..leaf
+                },
+            )
+        } else {
+            let h = rng.range(min_leaf..leaf.height - min_leaf + 1);
+            (
+                Room { height: h, ..leaf },
+                Room {
+                    y: leaf.y + h,
+                    height: leaf.height - h,
+                    ..leaf
+                },
+            )
+        };
+
+        let mut rooms = self.split(map, a, depth + 1, rng);
+        let other = self.split(map, b, depth + 1, rng);
+
+        let from = rooms[rng.range(0..rooms.len() as i32) as usize].center();
+        let to = other[rng.range(0..other.len() as i32) as usize].center();
+        carve_corridor(map, from, to, rng);
+
+        rooms.extend(other);
+        rooms
+    }
+
+    // random room inside the leaf, keeping its last row and column as wall
+    fn carve_room(&self, map: &mut Map, leaf: Room, rng: &mut TheRng) -> Room {
+        let width = rng.range(self.min_room.min(leaf.width - 1)..leaf.width);
+        let height = rng.range(self.min_room.min(leaf.height - 1)..leaf.height);
+        let room = Room {
+            x: leaf.x + rng.range(0..leaf.width - width),
+            y: leaf.y + rng.range(0..leaf.height - height),
+            width,
+            height,
+        };
+
+        for x in room.x..room.x + room.width {
+            for y in room.y..room.y + room.height {
+                let idx = map.idx(x, y);
+                map.cells[idx] = TileType::Floor;
+            }
+        }
+
+        room
+    }
+}
+
+impl MapGenerator for Rooms {
+    fn generate(&self, width: i32, height: i32, _level: i32, rng: &mut TheRng) -> Map {
+        let mut map = Map::filled(width, height, TileType::Wall);
+
+        // the right and bottom border come from the leaves' own wall
+        let interior = Room {
+            x: 1,
+            y: 1,
+            width: width - 1,
+            height: height - 1,
+        };
+        map.rooms = self.split(&mut map, interior, 0, rng);
+
+        map
+    }
+}
+
+fn carve_corridor(map: &mut Map, from: MapPosition, to: MapPosition, rng: &mut TheRng) {
+    // either leg may come first
+    let [corner, _] = corners(from, to, rng.coin_flip() == CoinFlip::Heads);
+    carve(map, &corridor(from, corner, to));
+}
+
+// the two corners an L-shaped corridor can turn at
+fn corners(from: MapPosition, to: MapPosition, across_first: bool) -> [MapPosition; 2] {
+    let across = MapPosition { x: to.x, y: from.y };
+    let down = MapPosition { x: from.x, y: to.y };
+    if across_first {
+        [across, down]
+    } else {
+        [down, across]
+    }
+}
+
+// the cells of a corridor from `from` to `to` turning at `corner`
+fn corridor(from: MapPosition, corner: MapPosition, to: MapPosition) -> Vec<MapPosition> {
+    let mut cells = Vec::new();
+    for (a, b) in [(from, corner), (corner, to)] {
+        for x in a.x.min(b.x)..=a.x.max(b.x) {
+            for y in a.y.min(b.y)..=a.y.max(b.y) {
+                cells.push(MapPosition { x, y });
+            }
+        }
+    }
+    cells
+}
+
+fn carve(map: &mut Map, cells: &[MapPosition]) {
+    for p in cells {
+        let idx = map.idx(p.x, p.y);
+        map.cells[idx] = TileType::Floor;
+    }
+}
+
+/// Stamps prefab rooms on top of another generator's map. Each stamped room
+/// is joined to the rest of the map by a corridor that goes around the ones
+/// stamped before it, and added to `Map::prefab_areas`.
+pub struct WithPrefabs<G> {
+    pub base: G,
+    pub prefabs: Vec<Prefab>,
+    /// prefabs stamped per map, fewer if they don't fit
+    pub count: usize,
+}
+
+impl<G: MapGenerator> WithPrefabs<G> {
+    const PLACEMENT_ATTEMPTS: usize = 10;
+
+    pub fn new(base: G, prefabs: Vec<Prefab>, count: usize) -> Self {
+        WithPrefabs {
+            base,
+            prefabs,
+            count,
+        }
+    }
+
+    // top left corner for `prefab` inside the border, clear of `taken`
+    fn place(&self, map: &Map, prefab: &Prefab, taken: &[Room], rng: &mut TheRng) -> Option<Room> {
+        for _ in 0..Self::PLACEMENT_ATTEMPTS {
+            let room = Room {
+                x: rng.range(1..map.width - prefab.width),
+                y: rng.range(1..map.height - prefab.height),
+                width: prefab.width,
+                height: prefab.height,
+            };
+            if !taken.iter().any(|t| t.overlaps(&room)) {
+                return Some(room);
+            }
+        }
+        None
+    }
+}
+
+impl<G: MapGenerator> MapGenerator for WithPrefabs<G> {
+    fn generate(&self, width: i32, height: i32, level: i32, rng: &mut TheRng) -> Map {
+        let mut map = self.base.generate(width, height, level, rng);
+
+        let fitting = self
+            .prefabs
+            .iter()
+            .filter(|p| p.width <= width - 2 && p.height <= height - 2)
+            .collect::<Vec<_>>();
+        if fitting.is_empty() {
+            return map;
+        }
+
+        let mut stamped = Vec::new();
+        for _ in 0..self.count {
+            let prefab = fitting[rng.range(0..fitting.len() as i32) as usize];
+            let Some(spot) = self.place(&map, prefab, &stamped, rng) else {
+                continue;
+            };
+
+            // somewhere open outside the prefab to connect it to
+            let outside = (0..map.cells.len())
+                .map(|idx| {
+                    let (x, y) = map.rev_idx(idx);
+                    MapPosition { x, y }
+                })
+                .filter(|&p| map.cells[map.idx(p.x, p.y)] == TileType::Floor && !spot.contains(p))
+                .collect::<Vec<_>>();
+
+            let room = prefab.stamp(&mut map, spot.x, spot.y);
+
+            // from the prefab's floor tile closest to its middle
+            let center = room.center();
+            let inside = (0..prefab.height)
+                .flat_map(|y| (0..prefab.width).map(move |x| (x, y)))
+                .filter(|&(x, y)| match prefab.tile(x, y) {
+                    // the corridor would pave over hazards
+                    PrefabTile::Tile(tile) => tile == TileType::Floor,
+                    PrefabTile::Keep => false,
+                    PrefabTile::Spawn(_) => true,
+                })
+                .map(|(x, y)| MapPosition {
+                    x: room.x + x,
+                    y: room.y + y,
+                })
+                .min_by_key(|p| (p.x - center.x).abs() + (p.y - center.y).abs());
+
+            if let (Some(from), false) = (inside, outside.is_empty()) {
+                let first = rng.range(0..outside.len() as i32) as usize;
+                let across_first = rng.coin_flip() == CoinFlip::Heads;
+                // the first corridor that keeps clear of the prefabs stamped
+                // before, so it can't break through their walls
+                let clear = (0..outside.len())
+                    .map(|i| outside[(first + i) % outside.len()])
+                    .flat_map(|to| corners(from, to, across_first).map(|c| corridor(from, c, to)))
+                    .find(|cells| {
+                        !cells
+                            .iter()
+                            .any(|&p| stamped.iter().any(|r: &Room| r.contains(p)))
+                    });
+                if let Some(cells) = clear {
+                    carve(&mut map, &cells);
+                }
+            }
+
+            map.prefab_areas.push(room);
+            stamped.push(room);
+        }
+
+        map
+    }
+}
+
+/// Which generator lays out which level, and what ends it. Each entry
+/// applies from its level up to the next entry's, the last one to every level
+/// after it.
+pub struct LevelProgression {
+    entries: Vec<(i32, Box<dyn MapGenerator>)>,
+    objectives: Vec<(i32, Objective)>,
+}
+
+impl Default for LevelProgression {
+    fn default() -> Self {
+        LevelProgression::new()
+            .then(1, ScatteredPillars)
+            .then(3, WithPrefabs::new(Caves::default(), Prefab::builtin(), 1))
+            .then(5, WithPrefabs::new(Rooms::default(), Prefab::builtin(), 1))
+            .objective_from(4, Objective::Either)
+    }
+}
+
+impl LevelProgression {
+    pub fn new() -> Self {
+        LevelProgression {
+            entries: Vec::new(),
+            objectives: Vec::new(),
+        }
+    }
+
+    /// Uses `generator` from `level` on. Entries have to be added in order.
+    pub fn then(mut self, level: i32, generator: impl MapGenerator + 'static) -> Self {
+        assert!(
+            self.entries.last().is_none_or(|&(l, _)| l < level),
+            "level progression out of order at level {}",
+            level
+        );
+
+        self.entries.push((level, Box::new(generator)));
+        self
+    }
+
+    /// Uses `objective` from `level` on, levels before the first one are
+    /// `Objective::ClearAll`. Entries have to be added in order.
+    pub fn objective_from(mut self, level: i32, objective: Objective) -> Self {
+        assert!(
+            self.objectives.last().is_none_or(|&(l, _)| l < level),
+            "level objectives out of order at level {}",
+            level
+        );
+
+        self.objectives.push((level, objective));
+        self
+    }
+
+    pub fn objective(&self, level: i32) -> Objective {
+        self.objectives
+            .iter()
+            .rev()
+            .find(|&&(from, _)| from <= level)
+            .map(|&(_, objective)| objective)
+            .unwrap_or_default()
+    }
+
+    pub fn generator(&self, level: i32) -> &dyn MapGenerator {
+        self.entries
+            .iter()
+            .rev()
+            .find(|&&(from, _)| from <= level)
+            .or(self.entries.first())
+            .map(|(_, generator)| generator.as_ref())
+            .expect("empty level progression")
+    }
+}
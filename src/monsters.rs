@@ -0,0 +1,351 @@
+//! Monster definitions, loaded from a JSON list such as the built-in
+//! `monsters.json`:
+//!
+//! ```json
+//! [
+//!     {
+//!         "id": "MonsterA",
+//!         "health": 1,
+//!         "speed": { "count": 1, "sides": 10, "times": 10 },
+//!         "sight": 8,
+//!         "damage": 1,
+//!         "spawn": [{ "from_level": 1, "weight": 50 }],
+//!         "frames": {
+//!             "stationary": [{ "x": 8, "y": 1, "ticks": 10 }],
+//!             "moving": [{ "x": 8, "y": 1 }]
+//!         },
+//!         "sounds": { "hurt": "hit1", "death": "explode1" }
+//!     }
+//! ]
+//! ```
+//!
+//! `speed` is the dice rolled for the ticks between two moves, `count` and
+//! `times` default to 1. `sight` is how far the monster sees at least, on
+//! deeper levels it sees as far as the level number. `damage` defaults to 1.
+//! `spawn` gives the monster's weight in the spawn roll from a level on, the
+//! last entry up to the level counts. Frames are cells of the sprite sheet,
+//! an animation cycles through them holding each for `ticks`, unless
+//! `animations.json` has clips for the monster. Sounds default to the ones
+//! above.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{animations::Frame, data, game::TheRng, rng::Rng};
+
+/// Name of a monster definition. Short enough to be copied around with the
+/// rest of `EntityType`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MonsterId {
+    len: u8,
+    bytes: [u8; MonsterId::MAX_LEN],
+}
+
+impl MonsterId {
+    pub const MAX_LEN: usize = 23;
+
+    /// `None` if `name` is empty or longer than `MAX_LEN` bytes.
+    pub fn new(name: &str) -> Option<MonsterId> {
+        if name.is_empty() || name.len() > MonsterId::MAX_LEN {
+            return None;
+        }
+
+        let mut bytes = [0; MonsterId::MAX_LEN];
+        bytes[..name.len()].copy_from_slice(name.as_bytes());
+        Some(MonsterId {
+            len: name.len() as u8,
+            bytes,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        // only ever built from a whole `&str`
+        std::str::from_utf8(&self.bytes[..self.len as usize]).unwrap()
+    }
+}
+
+impl fmt::Debug for MonsterId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MonsterId({:?})", self.as_str())
+    }
+}
+
+impl fmt::Display for MonsterId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Serialize for MonsterId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MonsterId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        MonsterId::new(&name).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "monster id {:?} has to be 1 to {} bytes long",
+                name,
+                MonsterId::MAX_LEN
+            ))
+        })
+    }
+}
+
+/// `count` dice with `sides` sides, the sum multiplied by `times`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct Dice {
+    #[serde(default = "one")]
+    pub count: i32,
+    pub sides: i32,
+    #[serde(default = "one")]
+    pub times: i32,
+}
+
+impl Dice {
+    pub fn roll(&self, rng: &mut TheRng) -> i32 {
+        (0..self.count)
+            .map(|_| rng.range(1..self.sides + 1))
+            .sum::<i32>()
+            * self.times
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub struct SpawnWeight {
+    pub from_level: i32,
+    pub weight: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct Frames {
+    pub stationary: Vec<Frame>,
+    pub moving: Vec<Frame>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct MonsterSounds {
+    #[serde(default = "hurt_sound")]
+    pub hurt: String,
+    #[serde(default = "death_sound")]
+    pub death: String,
+}
+
+impl Default for MonsterSounds {
+    fn default() -> Self {
+        MonsterSounds {
+            hurt: hurt_sound(),
+            death: death_sound(),
+        }
+    }
+}
+
+fn one() -> i32 {
+    1
+}
+
+fn hurt_sound() -> String {
+    "hit1".to_owned()
+}
+
+fn death_sound() -> String {
+    "explode1".to_owned()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct MonsterDef {
+    pub id: MonsterId,
+    pub health: i32,
+    /// ticks between two moves
+    pub speed: Dice,
+    pub sight: i32,
+    #[serde(default = "one")]
+    pub damage: i32,
+    pub spawn: Vec<SpawnWeight>,
+    pub frames: Frames,
+    #[serde(default)]
+    pub sounds: MonsterSounds,
+}
+
+impl MonsterDef {
+    /// Weight in the spawn roll on `level`.
+    pub fn spawn_weight(&self, level: i32) -> i32 {
+        self.spawn
+            .iter()
+            .take_while(|s| s.from_level <= level)
+            .last()
+            .map_or(0, |s| s.weight)
+    }
+
+    /// How far it sees on `level`.
+    pub fn sight_range(&self, level: i32) -> i32 {
+        self.sight.max(level.min(50))
+    }
+}
+
+#[derive(Debug)]
+pub enum MonsterError {
+    Io(PathBuf, io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for MonsterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MonsterError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            MonsterError::Json(e) => write!(f, "{}", e),
+            MonsterError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MonsterError {}
+
+impl From<serde_json::Error> for MonsterError {
+    fn from(e: serde_json::Error) -> Self {
+        MonsterError::Json(e)
+    }
+}
+
+fn invalid(msg: impl Into<String>) -> MonsterError {
+    MonsterError::Invalid(msg.into())
+}
+
+/// Every monster there is, in the order they're listed. Defaults to the
+/// built-in list.
+#[derive(Clone, Debug)]
+pub struct Monsters {
+    defs: Vec<MonsterDef>,
+}
+
+impl Default for Monsters {
+    fn default() -> Self {
+        Monsters::from_json(data::MONSTERS).expect("built-in monsters.json is invalid")
+    }
+}
+
+impl Monsters {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MonsterError> {
+        let path = path.as_ref();
+        let json =
+            std::fs::read_to_string(path).map_err(|e| MonsterError::Io(path.to_owned(), e))?;
+        Monsters::from_json(&json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, MonsterError> {
+        let defs: Vec<MonsterDef> = serde_json::from_str(json)?;
+        let sounds = data::SOUNDS
+            .iter()
+            .map(|&(name, _)| name)
+            .collect::<Vec<_>>();
+
+        for (i, def) in defs.iter().enumerate() {
+            let id = def.id;
+            if defs[..i].iter().any(|d| d.id == id) {
+                return Err(invalid(format!("monster {} is defined twice", id)));
+            }
+            if def.health < 1 {
+                return Err(invalid(format!("monster {} needs some health", id)));
+            }
+            let Dice {
+                count,
+                sides,
+                times,
+            } = def.speed;
+            if count < 1 || sides < 1 || times < 1 {
+                return Err(invalid(format!("monster {} has an unrollable speed", id)));
+            }
+            if def
+                .spawn
+                .windows(2)
+                .any(|w| w[0].from_level >= w[1].from_level)
+            {
+                return Err(invalid(format!(
+                    "spawn weights of monster {} are out of order",
+                    id
+                )));
+            }
+            if def.spawn.iter().any(|s| s.weight < 0) {
+                return Err(invalid(format!("monster {} has a negative weight", id)));
+            }
+            for (action, frames) in [
+                ("stationary", &def.frames.stationary),
+                ("moving", &def.frames.moving),
+            ] {
+                if frames.is_empty() {
+                    return Err(invalid(format!("monster {} has no {} frames", id, action)));
+                }
+                if let Some(f) = frames.iter().find(|f| !f.on_sheet()) {
+                    return Err(invalid(format!(
+                        "monster {} frame {}, {} is off the sprite sheet",
+                        id, f.x, f.y
+                    )));
+                }
+                if frames.len() > 1 && frames.iter().any(|f| f.ticks < 1) {
+                    return Err(invalid(format!(
+                        "monster {} has {} frames without ticks",
+                        id, action
+                    )));
+                }
+            }
+            for sound in [&def.sounds.hurt, &def.sounds.death] {
+                if !sounds.contains(&sound.as_str()) {
+                    return Err(invalid(format!(
+                        "monster {} has an unknown sound {}",
+                        id, sound
+                    )));
+                }
+            }
+        }
+
+        let monsters = Monsters { defs };
+
+        // every level from the first on has to have something to roll
+        let mut levels = monsters
+            .defs
+            .iter()
+            .flat_map(|d| d.spawn.iter().map(|s| s.from_level.max(1)))
+            .collect::<Vec<_>>();
+        levels.push(1);
+        if let Some(&level) = levels.iter().find(|&&l| monsters.total_weight(l) == 0) {
+            return Err(invalid(format!("no monster can spawn on level {}", level)));
+        }
+
+        Ok(monsters)
+    }
+
+    pub fn get(&self, id: MonsterId) -> Option<&MonsterDef> {
+        self.defs.iter().find(|d| d.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &MonsterDef> {
+        self.defs.iter()
+    }
+
+    /// Picks a monster for `level` by spawn weight.
+    pub fn roll(&self, level: i32, rng: &mut TheRng) -> &MonsterDef {
+        let mut roll = rng.range(0..self.total_weight(level).max(1));
+        for def in self.defs.iter() {
+            let weight = def.spawn_weight(level);
+            if roll < weight {
+                return def;
+            }
+            roll -= weight;
+        }
+
+        // only on levels below the first
+        &self.defs[0]
+    }
+
+    fn total_weight(&self, level: i32) -> i32 {
+        self.defs.iter().map(|d| d.spawn_weight(level)).sum()
+    }
+}
@@ -0,0 +1,178 @@
+//! Hand made rooms stamped into generated maps.
+//!
+//! A prefab is a block of text, one character per tile:
+//!
+//! ```text
+//! #  wall             .  floor           (space)  keep what's there
+//! D  closed door      '  open door       %        cracked wall
+//! ~  water            ^  lava            ,        rubble
+//! >  exit             <  stairs up
+//! @  player start     e  any enemy       A B C    monster MonsterA, B or C
+//! ?  any item         $  ammo            +        health
+//! ```
+//!
+//! Markers are floor tiles. Lines starting with `;` are comments. Marked
+//! spawns go through the same rules as random ones in `spawn`, so a marker on
+//! an occupied or unreachable tile, or an enemy in the player's start room, is
+//! skipped.
+
+use std::fmt;
+
+use crate::components::{EntityType, MapPosition};
+use crate::data::PREFABS;
+use crate::map::{Map, Room, SpawnKind, SpawnPoint, TileType};
+use crate::monsters::MonsterId;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PrefabTile {
+    Keep,
+    Tile(TileType),
+    Spawn(SpawnKind),
+}
+
+#[derive(Clone, Debug)]
+pub struct Prefab {
+    pub name: String,
+    pub width: i32,
+    pub height: i32,
+    tiles: Vec<PrefabTile>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrefabError {
+    Empty { name: String },
+    UnknownTile { name: String, line: usize, ch: char },
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefabError::Empty { name } => write!(f, "prefab {} has no tiles", name),
+            PrefabError::UnknownTile { name, line, ch } => {
+                write!(f, "prefab {} line {}: unknown tile {:?}", name, line, ch)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrefabError {}
+
+impl Prefab {
+    pub fn parse(name: &str, text: &str) -> Result<Prefab, PrefabError> {
+        use EntityType::*;
+        use PrefabTile::*;
+        use SpawnKind::*;
+
+        let monster = |id| Spawn(Enemy(MonsterId::new(id)));
+
+        let mut rows = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+            if line.starts_with(';') || (line.is_empty() && rows.is_empty()) {
+                continue;
+            }
+
+            let row = line
+                .chars()
+                .map(|ch| {
+                    Ok(match ch {
+                        ' ' => Keep,
+                        '#' => Tile(TileType::Wall),
+                        '.' => Tile(TileType::Floor),
+                        'D' => Tile(TileType::DoorClosed),
+                        '\'' => Tile(TileType::DoorOpen),
+                        '%' => Tile(TileType::CrackedWall),
+                        '~' => Tile(TileType::Water),
+                        '^' => Tile(TileType::Lava),
+                        ',' => Tile(TileType::Rubble),
+                        '>' => Tile(TileType::Exit),
+                        '<' => Tile(TileType::StairsUp),
+                        '@' => Spawn(PlayerStart),
+                        'e' => Spawn(Enemy(None)),
+                        'A' => monster("MonsterA"),
+                        'B' => monster("MonsterB"),
+                        'C' => monster("MonsterC"),
+                        '?' => Spawn(Item(None)),
+                        '$' => Spawn(Item(Some(Ammo))),
+                        '+' => Spawn(Item(Some(Health))),
+                        ch => {
+                            return Err(PrefabError::UnknownTile {
+                                name: name.to_owned(),
+                                line: n + 1,
+                                ch,
+                            })
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push(row);
+        }
+
+        while rows.last().is_some_and(|r| r.is_empty()) {
+            rows.pop();
+        }
+
+        let width = rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        if width == 0 {
+            return Err(PrefabError::Empty {
+                name: name.to_owned(),
+            });
+        }
+
+        let height = rows.len();
+        let mut tiles = Vec::with_capacity(width * height);
+        for mut row in rows {
+            row.resize(width, Keep);
+            tiles.extend(row);
+        }
+
+        Ok(Prefab {
+            name: name.to_owned(),
+            width: width as i32,
+            height: height as i32,
+            tiles,
+        })
+    }
+
+    /// The prefabs embedded from `data::PREFABS`.
+    pub fn builtin() -> Vec<Prefab> {
+        PREFABS
+            .iter()
+            .map(|(name, text)| Prefab::parse(name, text).unwrap())
+            .collect()
+    }
+
+    pub fn tile(&self, x: i32, y: i32) -> PrefabTile {
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    /// Copies the prefab onto `map` with its top left corner at `(x, y)` and
+    /// adds its markers to `map.spawns`. Returns the area it covers.
+    pub fn stamp(&self, map: &mut Map, x: i32, y: i32) -> Room {
+        for py in 0..self.height {
+            for px in 0..self.width {
+                let pos = MapPosition {
+                    x: x + px,
+                    y: y + py,
+                };
+                let idx = map.idx(pos.x, pos.y);
+
+                match self.tile(px, py) {
+                    PrefabTile::Keep => (),
+                    PrefabTile::Tile(tile) => map.cells[idx] = tile,
+                    PrefabTile::Spawn(kind) => {
+                        map.cells[idx] = TileType::Floor;
+                        map.spawns.push(SpawnPoint { pos, kind });
+                    }
+                }
+            }
+        }
+
+        Room {
+            x,
+            y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
@@ -1,24 +1,66 @@
 use std::ops::DerefMut;
 
 use embedded_graphics::{mono_font, text::Alignment};
-use pixels::{Error, Pixels};
+use image::RgbaImage;
 use types::RenderPosition;
 use world::SubWorld;
 
 use crate::map::TileType;
-use crate::resources::LevelStats;
+use crate::resources::{LevelStats, TickAlpha};
+use crate::rng::RunSeed;
 use crate::ScheduleName;
 use crate::{
     components::*,
+    data::SPRITES,
     draw,
-    game::{CoreGame, GameState, SpriteGrid},
-    map::{self, Map, MapViewport},
+    game::{GameState, SpriteGrid},
+    map::{self, Map, MapViewport, Projection},
     shapes::{self, FrameBufferTarget},
+    simulation::Simulation,
+    sprites::{get_draw_layer, DrawLayer},
     SCREEN_HEIGHT, SCREEN_WIDTH,
 };
 use legion::*;
 
-pub fn do_render(game: &mut CoreGame) -> Result<(), Error> {
+/// Loads the sprite sheet and creates the CPU side render targets the render
+/// systems draw into.
+pub fn insert_render_resources(resources: &mut Resources) {
+    use image::ImageReader;
+
+    let img = ImageReader::new(std::io::Cursor::new(SPRITES))
+        .with_guessed_format()
+        .unwrap()
+        .decode()
+        .unwrap()
+        .to_rgb8();
+    let sprite_grid = SpriteGrid::new(img, 16, 16, 32, 32 * 3);
+
+    let shapes = FrameBufferTarget::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let frame = RgbaImage::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+
+    // unless one was loaded from an Aseprite sheet already
+    if !resources.contains::<SpriteGrid>() {
+        resources.insert(sprite_grid);
+    }
+    resources.insert(shapes);
+    resources.insert(frame);
+    resources.insert(TickAlpha::default());
+    resources.insert(Projection::default());
+}
+
+/// Renders the current world state off-screen and returns a copy of the frame.
+pub fn render_frame(game: &mut Simulation) -> RgbaImage {
+    if !game.resources.contains::<RgbaImage>() {
+        insert_render_resources(&mut game.resources);
+    }
+
+    do_render(game);
+
+    game.resources.get::<RgbaImage>().unwrap().clone()
+}
+
+/// Draws the current game state into the `RgbaImage` frame resource.
+pub fn do_render(game: &mut Simulation) {
     use GameState::*;
 
     let gamestate = *game.resources.get::<GameState>().unwrap();
@@ -37,23 +79,36 @@ pub fn do_render(game: &mut CoreGame) -> Result<(), Error> {
         }
         _ => {}
     }
-
-    let pixels = game.resources.get_mut::<Pixels>().unwrap();
-    pixels.render()
 }
 
+/// How high walls stand in isometric mode, in pixels.
+const ISO_WALL_HEIGHT: i32 = 8;
+
 #[system]
 pub fn draw_map(
-    #[resource] pixels: &mut Pixels,
+    #[resource] frame: &mut RgbaImage,
     #[resource] sprite_grid: &SpriteGrid,
     #[resource] viewport: &MapViewport,
     #[resource] map: &Map,
+    #[resource] projection: &Projection,
 ) {
-    use map::TileType::*;
-
-    let frame = pixels.frame_mut();
+    let frame: &mut [u8] = frame;
     frame.iter_mut().for_each(|x| *x = 0);
 
+    match projection {
+        Projection::TopDown => draw_map_top_down(frame, sprite_grid, viewport, map),
+        Projection::Isometric => draw_map_isometric(frame, sprite_grid, viewport, map),
+    }
+}
+
+fn draw_map_top_down(
+    frame: &mut [u8],
+    sprite_grid: &SpriteGrid,
+    viewport: &MapViewport,
+    map: &Map,
+) {
+    use map::TileType::*;
+
     let y_offset = 2 * 16;
 
     for screen_x in -1..viewport.width + 1 {
@@ -61,13 +116,34 @@ pub fn draw_map(
             if let Some((MapPosition { x: map_x, y: map_y }, (xfrac, yfrac))) =
                 viewport.viewport_to_map_pos(screen_x, screen_y, map)
             {
-                // draw floor
+                // seen tiles as they are, remembered ones dimmed
                 let idx = map.idx(map_x, map_y);
-                let (six, siy) = (1, 0); // floor
-                if map.visible_cells[idx] {
-                    let xoff = screen_x * 16 - (xfrac * 16.0) as i32;
-                    let yoff = screen_y * 16 - (yfrac * 16.0) as i32;
-                    draw::blit_sprite(
+                let blit = if map.visible_cells[idx] {
+                    draw::blit_sprite
+                } else if map.revealed_cells[idx] {
+                    draw::blit_sprite_dimmed
+                } else {
+                    continue;
+                };
+
+                // draw floor, then whatever is on top of it
+                let sprites = match map.cells[idx] {
+                    Floor => vec![],
+                    Wall => vec![get_wall_tile(map, map_x, map_y)],
+                    CrackedWall => vec![get_wall_tile(map, map_x, map_y), (2, 5)],
+                    DoorClosed => vec![(2, 0)],
+                    DoorOpen => vec![(2, 1)],
+                    Water => vec![(2, 2)],
+                    Lava => vec![(2, 3)],
+                    Rubble => vec![(2, 4)],
+                    Exit => vec![(2, 6)],
+                    StairsUp => vec![(2, 7)],
+                };
+
+                let xoff = screen_x * 16 - (xfrac * 16.0) as i32;
+                let yoff = screen_y * 16 - (yfrac * 16.0) as i32;
+                for (six, siy) in std::iter::once((1, 0)).chain(sprites) {
+                    blit(
                         sprite_grid,
                         six,
                         siy,
@@ -78,33 +154,86 @@ pub fn draw_map(
                         SCREEN_HEIGHT,
                         false,
                     );
-
-                    // draw wall
-                    let typ = map.cells[idx];
-                    if typ == Wall {
-                        let (six, siy) = get_wall_tile(map, map_x, map_y);
-
-                        let xoff = screen_x * 16 - (xfrac * 16.0) as i32;
-                        let yoff = screen_y * 16 - (yfrac * 16.0) as i32;
-                        draw::blit_sprite(
-                            sprite_grid,
-                            six,
-                            siy,
-                            xoff,
-                            yoff + y_offset,
-                            frame,
-                            SCREEN_WIDTH,
-                            SCREEN_HEIGHT,
-                            false,
-                        );
-                    }
                 }
             }
         }
     }
 }
 
-fn render_finish(game: &mut CoreGame) {
+// every tile on screen, by diagonal from the back corner of the map so that
+// walls in front cover what's behind them
+fn draw_map_isometric(
+    frame: &mut [u8],
+    sprite_grid: &SpriteGrid,
+    viewport: &MapViewport,
+    map: &Map,
+) {
+    use map::TileType::*;
+
+    let y_offset = 2 * 16;
+
+    for diagonal in 0..map.width + map.height - 1 {
+        let first = (diagonal - map.height + 1).max(0);
+        for map_x in first..=diagonal.min(map.width - 1) {
+            let map_y = diagonal - map_x;
+            let idx = map.idx(map_x, map_y);
+            let blit = if map.visible_cells[idx] {
+                draw::blit_tile_iso
+            } else if map.revealed_cells[idx] {
+                draw::blit_tile_iso_dimmed
+            } else {
+                continue;
+            };
+
+            let RenderPosition { x, y } = viewport.map_to_screen_pos(
+                MapPosition { x: map_x, y: map_y },
+                16.0,
+                Projection::Isometric,
+            );
+            let y = y + y_offset;
+            if x + 16 <= 0
+                || x >= SCREEN_WIDTH as i32
+                || y + 8 <= 0
+                || y - ISO_WALL_HEIGHT >= SCREEN_HEIGHT as i32
+            {
+                continue;
+            }
+
+            // the wall pieces only make sense seen from above, blocks get the
+            // plain one
+            let (on_floor, sprites, height) = match map.cells[idx] {
+                Floor => (true, vec![], 0),
+                Wall => (false, vec![(0, 3)], ISO_WALL_HEIGHT),
+                CrackedWall => (false, vec![(0, 3), (2, 5)], ISO_WALL_HEIGHT),
+                DoorClosed => (true, vec![(2, 0)], ISO_WALL_HEIGHT),
+                DoorOpen => (true, vec![(2, 1)], 0),
+                Water => (true, vec![(2, 2)], 0),
+                Lava => (true, vec![(2, 3)], 0),
+                Rubble => (true, vec![(2, 4)], 0),
+                Exit => (true, vec![(2, 6)], 0),
+                StairsUp => (true, vec![(2, 7)], 0),
+            };
+
+            let floor = on_floor.then_some(((1, 0), 0));
+            let on_top = sprites.into_iter().map(|sprite| (sprite, height));
+            for ((six, siy), height) in floor.into_iter().chain(on_top) {
+                blit(
+                    sprite_grid,
+                    six,
+                    siy,
+                    x,
+                    y,
+                    height,
+                    frame,
+                    SCREEN_WIDTH,
+                    SCREEN_HEIGHT,
+                );
+            }
+        }
+    }
+}
+
+fn render_finish(game: &mut Simulation) {
     let font = mono_font::ascii::FONT_9X18;
 
     let mut shapes = game.resources.get_mut::<FrameBufferTarget>().unwrap();
@@ -113,7 +242,7 @@ fn render_finish(game: &mut CoreGame) {
 
     let mut score = 0;
     let mut query = <(&PlayerFlag, &Score)>::query();
-    for (_player_flag, &Score(s)) in query.iter(&mut game.world) {
+    for (_player_flag, &Score(s)) in query.iter(&game.world) {
         score = s;
     }
 
@@ -138,16 +267,36 @@ fn render_finish(game: &mut CoreGame) {
         font,
     );
 
-    let mut pixels = game.resources.get_mut::<Pixels>().unwrap();
+    if let Some(RunSeed(seed)) = game.resources.get::<RunSeed>().as_deref() {
+        let font = mono_font::ascii::FONT_6X9;
+
+        // long JSON seeds get wrapped to fit the screen
+        let seed_str = format!("Seed: {}", seed);
+        let chars = seed_str.chars().collect::<Vec<_>>();
+        for (i, line) in chars.chunks(50).enumerate() {
+            let line = line.iter().collect::<String>();
+            shapes::draw_text(
+                shapes.deref_mut(),
+                &line,
+                160,
+                95 + i as i32 * 10,
+                (230, 230, 230),
+                Alignment::Center,
+                font,
+            );
+        }
+    }
 
-    shapes.flush(pixels.frame_mut());
+    let mut frame = game.resources.get_mut::<RgbaImage>().unwrap();
+
+    shapes.flush(&mut frame);
 }
 
 #[system]
 pub fn new_level_text(
     #[resource] mut shapes: &mut FrameBufferTarget,
     #[resource] level_stats: &LevelStats,
-    #[resource] pixels: &mut Pixels,
+    #[resource] frame: &mut RgbaImage,
 ) {
     shapes.clear();
 
@@ -165,7 +314,18 @@ pub fn new_level_text(
         font,
     );
 
-    shapes.flush(pixels.frame_mut());
+    shapes.flush(frame);
+}
+
+// one sprite in the draw list
+struct SpriteDraw {
+    // screen row of the ground under the sprite, movement included
+    row: i32,
+    layer: DrawLayer,
+    x: i32,
+    y: i32,
+    sprite: (u8, u8),
+    flip_y: bool,
 }
 
 #[system]
@@ -174,79 +334,118 @@ pub fn new_level_text(
 #[read_component(RenderableSprite)]
 #[read_component(OnlyVisibleInPlayerFOV)]
 #[read_component(ScreenDrawOffset)]
+#[read_component(EntityType)]
 pub fn draw_renderables(
     world: &SubWorld,
     #[resource] viewport: &MapViewport,
     #[resource] map: &Map,
-    #[resource] pixels: &mut Pixels,
+    #[resource] frame: &mut RgbaImage,
     #[resource] sprite_grid: &SpriteGrid,
+    #[resource] &TickAlpha(alpha): &TickAlpha,
+    #[resource] &projection: &Projection,
 ) {
     let y_offset = 2 * 16;
 
+    // isometric sprites stand on the middle of their tile
+    let lift = match projection {
+        Projection::TopDown => 0,
+        Projection::Isometric => 12,
+    };
+    let mut draws = Vec::new();
+
     let mut query = <(
         &MapPosition,
         &RenderableSprite,
+        Option<&EntityType>,
         Option<&OnlyVisibleInPlayerFOV>,
         Option<&Moving>,
         Option<&ScreenDrawOffset>,
     )>::query();
-    query.for_each(world, |(pos, renderable, only_in_fov, moving, offset)| {
-        let &MapPosition { x: map_x, y: map_y } = pos;
+    query.for_each(
+        world,
+        |(pos, renderable, entity_type, only_in_fov, moving, offset)| {
+            let &MapPosition { x: map_x, y: map_y } = pos;
+
+            if let Some(RenderPosition { mut x, mut y }) = viewport.checked_map_to_screen_pos(
+                *pos,
+                16.0,
+                RenderPosition {
+                    x: SCREEN_WIDTH as i32,
+                    y: SCREEN_HEIGHT as i32,
+                },
+                projection,
+            ) {
+                let &RenderableSprite {
+                    sprite_x,
+                    sprite_y,
+                    flip,
+                    ..
+                } = renderable;
 
-        if let Some(RenderPosition { mut x, mut y }) = viewport.checked_map_to_screen_pos(
-            *pos,
-            16.0,
-            RenderPosition {
-                x: SCREEN_WIDTH as i32,
-                y: SCREEN_HEIGHT as i32,
-            },
-        ) {
-            let &RenderableSprite {
-                sprite_x,
-                sprite_y,
-                facing,
-            } = renderable;
-
-            let idx = map.idx(map_x, map_y);
-            if only_in_fov.is_some() && !map.visible_cells[idx] {
-                return;
-            }
+                let idx = map.idx(map_x, map_y);
+                if only_in_fov.is_some() && !map.visible_cells[idx] {
+                    return;
+                }
 
-            let flip_y = match facing {
-                types::Facing::Right => true,
-                _ => false,
-            };
+                // adjust x and y by moving delta, including the part of the next
+                // tick that has already passed
+                if let Some(&Moving {
+                    ticks_left,
+                    total_ticks,
+                    delta,
+                }) = moving
+                {
+                    let progress =
+                        ((total_ticks - ticks_left) as f32 + alpha).min(total_ticks as f32);
+                    let (dx, dy) = projection.offset(progress * delta.x, progress * delta.y);
+                    x += dx.round() as i32;
+                    y += dy.round() as i32;
+                }
+                let row = y;
 
-            // adjust x and y by moving delta
-            if let Some(&Moving {
-                ticks_left,
-                total_ticks,
-                delta,
-            }) = moving
-            {
-                x += ((total_ticks - ticks_left) as f32 * delta.x).round() as i32;
-                y += ((total_ticks - ticks_left) as f32 * delta.y).round() as i32;
-            }
+                // adjust by screen draw offset if applicable
+                if let Some(&ScreenDrawOffset { x: xoff, y: yoff }) = offset {
+                    let (xoff, yoff) = projection.offset(xoff as f32, yoff as f32);
+                    x += xoff.round() as i32;
+                    y += yoff.round() as i32;
+                }
 
-            // adjust by screen draw offset if applicable
-            if let Some(&ScreenDrawOffset { x: xoff, y: yoff }) = offset {
-                x += xoff;
-                y += yoff;
+                draws.push(SpriteDraw {
+                    row,
+                    layer: entity_type.map_or(DrawLayer::Actor, get_draw_layer),
+                    x,
+                    y: y - lift,
+                    sprite: (sprite_x, sprite_y),
+                    flip_y: flip,
+                });
             }
+        },
+    );
 
-            draw::blit_sprite(
-                sprite_grid,
-                sprite_x as u32,
-                sprite_y as u32,
-                x,
-                y + y_offset,
-                pixels.frame_mut(),
-                SCREEN_WIDTH,
-                SCREEN_HEIGHT,
-                flip_y,
-            );
-        }
-    });
+    // back to front, then by layer. The rest only breaks ties between
+    // otherwise equal sprites so that query order never shows.
+    draws.sort_by_key(|d| (d.row, d.layer, d.x, d.sprite, d.flip_y));
+
+    for SpriteDraw {
+        x,
+        y,
+        sprite: (sprite_x, sprite_y),
+        flip_y,
+        ..
+    } in draws
+    {
+        draw::blit_sprite(
+            sprite_grid,
+            sprite_x as u32,
+            sprite_y as u32,
+            x,
+            y + y_offset,
+            frame,
+            SCREEN_WIDTH,
+            SCREEN_HEIGHT,
+            flip_y,
+        );
+    }
 }
 
 #[system]
@@ -258,12 +457,13 @@ pub fn draw_text(
     world: &SubWorld,
     #[resource] mut shapes: &mut FrameBufferTarget,
     #[resource] viewport: &MapViewport,
-    #[resource] pixels: &mut Pixels,
+    #[resource] frame: &mut RgbaImage,
+    #[resource] &projection: &Projection,
 ) {
     shapes.clear();
 
     let mut query = <(&MapPosition, &TextBlock, Option<&Moving>)>::query();
-    query.for_each(world, |(pos, text, moving)| {
+    query.for_each(world, |(pos, text, _moving)| {
         //let font = embedded_graphics::mono_font::iso_8859_10::FONT_4X6;
         let font = embedded_graphics::mono_font::iso_8859_10::FONT_6X9;
         //let font = IBM437_8X8_REGULAR;
@@ -275,6 +475,7 @@ pub fn draw_text(
                 x: SCREEN_WIDTH as i32,
                 y: SCREEN_HEIGHT as i32,
             },
+            projection,
         ) {
             let &TextBlock {
                 ref text,
@@ -302,7 +503,7 @@ pub fn draw_text(
         shapes::draw_text(shapes.deref_mut(), text, x, y, color, alignment, font);
     });
 
-    shapes.flush(pixels.frame_mut());
+    shapes.flush(frame);
 }
 
 fn get_wall_tile(map: &Map, x: i32, y: i32) -> (u32, u32) {
@@ -310,7 +511,7 @@ fn get_wall_tile(map: &Map, x: i32, y: i32) -> (u32, u32) {
         ($dx:expr, $dy:expr) => {{
             map.cells
                 .get(map.idx(x + $dx, y + $dy))
-                .map(|t| *t == TileType::Wall)
+                .map(|t| matches!(t, TileType::Wall | TileType::CrackedWall))
                 .unwrap_or(true)
         }};
     }
@@ -0,0 +1,219 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use log::warn;
+
+use crate::{
+    game,
+    input::{ActionSet, TickInput},
+    rng::RunSeed,
+    simulation::Simulation,
+};
+
+const MAGIC: &[u8; 4] = b"CSRP";
+// 2 adds the data files
+const FORMAT_VERSION: u8 = 2;
+
+/// Every tick's input of a run plus what's needed to restart it. Identical
+/// consecutive inputs are stored as a single run, which keeps held movement
+/// and idle stretches small. The data files the run was recorded with are only
+/// kept as hashes, playback loads them from the working directory again.
+///
+/// File layout, little endian:
+///
+/// ```text
+/// "CSRP" u8:format_version
+/// u16:len game_version   u16:len seed
+/// u16:file_count { u16:len path u64:hash }*
+/// u32:run_count { u32:ticks u16:held u16:pressed }*
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Replay {
+    pub game_version: String,
+    pub seed: String,
+    /// see `game::data_files`
+    pub data: Vec<(String, u64)>,
+    runs: Vec<(u32, TickInput)>,
+}
+
+impl Replay {
+    pub fn new(seed: &str) -> Self {
+        Replay {
+            game_version: env!("CARGO_PKG_VERSION").to_owned(),
+            seed: seed.to_owned(),
+            data: game::data_files(),
+            runs: Vec::new(),
+        }
+    }
+
+    /// Starts a recording for a simulation created with `Simulation::from_seed`,
+    /// with the data files in the working directory loaded.
+    pub fn for_simulation(sim: &Simulation) -> Self {
+        let seed = sim.resources.get::<RunSeed>().unwrap();
+        Replay::new(&seed.0)
+    }
+
+    pub fn record(&mut self, input: TickInput) {
+        match self.runs.last_mut() {
+            Some((ticks, last)) if *last == input && *ticks < u32::MAX => *ticks += 1,
+            _ => self.runs.push((1, input)),
+        }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.runs.iter().map(|&(ticks, _)| ticks as u64).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    pub fn inputs(&self) -> impl Iterator<Item = TickInput> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|&(ticks, input)| std::iter::repeat_n(input, ticks as usize))
+    }
+
+    /// Data files that were added, removed or changed since the recording,
+    /// given the ones there are now.
+    pub fn changed_data(&self, data: &[(String, u64)]) -> Vec<String> {
+        fn hash(files: &[(String, u64)], path: &str) -> Option<u64> {
+            files.iter().find(|(p, _)| p == path).map(|&(_, hash)| hash)
+        }
+
+        let mut paths = self
+            .data
+            .iter()
+            .chain(data)
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths.dedup();
+        paths.retain(|path| hash(&self.data, path) != hash(data, path));
+        paths
+    }
+
+    /// Creates the simulation the recording started from, with the data files
+    /// in the working directory.
+    pub fn start(&self) -> Result<Simulation, serde_json::Error> {
+        if self.game_version != env!("CARGO_PKG_VERSION") {
+            warn!(
+                "replay recorded with version {}, running {}",
+                self.game_version,
+                env!("CARGO_PKG_VERSION")
+            );
+        }
+
+        let changed = self.changed_data(&game::data_files());
+        if !changed.is_empty() {
+            warn!(
+                "replay recorded with other data, changed: {}",
+                changed.join(", ")
+            );
+        }
+
+        let mut sim = Simulation::from_seed(&self.seed)?;
+        game::load_data(&mut sim);
+
+        Ok(sim)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Replay::read(&mut BufReader::new(File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[FORMAT_VERSION])?;
+        write_str(w, &self.game_version)?;
+        write_str(w, &self.seed)?;
+
+        let file_count =
+            u16::try_from(self.data.len()).map_err(|_| invalid_data("too many files"))?;
+        w.write_all(&file_count.to_le_bytes())?;
+        for (path, hash) in self.data.iter() {
+            write_str(w, path)?;
+            w.write_all(&hash.to_le_bytes())?;
+        }
+
+        w.write_all(&(self.runs.len() as u32).to_le_bytes())?;
+        for (ticks, input) in self.runs.iter() {
+            w.write_all(&ticks.to_le_bytes())?;
+            w.write_all(&input.held.0.to_le_bytes())?;
+            w.write_all(&input.pressed.0.to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(r: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a replay file"));
+        }
+
+        let [format_version] = read_array(r)?;
+        if format_version != FORMAT_VERSION {
+            return Err(invalid_data("unsupported replay format version"));
+        }
+
+        let game_version = read_str(r)?;
+        let seed = read_str(r)?;
+
+        let file_count = u16::from_le_bytes(read_array(r)?);
+        let mut data = Vec::new();
+        for _ in 0..file_count {
+            let path = read_str(r)?;
+            data.push((path, u64::from_le_bytes(read_array(r)?)));
+        }
+
+        let run_count = u32::from_le_bytes(read_array(r)?);
+        let mut runs = Vec::new();
+        for _ in 0..run_count {
+            let ticks = u32::from_le_bytes(read_array(r)?);
+            let held = ActionSet(u16::from_le_bytes(read_array(r)?));
+            let pressed = ActionSet(u16::from_le_bytes(read_array(r)?));
+            runs.push((ticks, TickInput { held, pressed }));
+        }
+
+        Ok(Replay {
+            game_version,
+            seed,
+            data,
+            runs,
+        })
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn read_array<const N: usize>(r: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> io::Result<()> {
+    let len = u16::try_from(s.len()).map_err(|_| invalid_data("string too long"))?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_str(r: &mut impl Read) -> io::Result<String> {
+    let len = u16::from_le_bytes(read_array(r)?) as usize;
+    let mut buf = vec![0; len];
+    r.read_exact(&mut buf)?;
+    String::from_utf8(buf).map_err(|_| invalid_data("invalid utf-8"))
+}
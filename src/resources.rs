@@ -1,17 +1,42 @@
-use std::collections::HashMap;
-
 use legion::*;
+use serde::{Deserialize, Serialize};
 
+use crate::audio::{AudioBackend, NullAudio};
 use crate::components::*;
 
 pub struct PlayerEntity(pub Entity);
 
-#[derive(Default)]
+/// What ends a level.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Objective {
+    /// killing every enemy
+    #[default]
+    ClearAll,
+    /// walking onto the exit, enemies or not
+    ReachExit,
+    /// whichever of the two comes first
+    Either,
+    /// walking onto the exit once every enemy is dead
+    Both,
+}
+
+impl Objective {
+    pub fn has_exit(self) -> bool {
+        self != Objective::ClearAll
+    }
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct LevelStats {
     pub level: i32,
+    #[serde(default)]
+    pub objective: Objective,
+    /// set once every enemy on the level is dead
+    #[serde(default)]
+    pub cleared: bool,
 }
 
-#[derive(Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct PlayerPosition(pub MapPosition, pub Option<MapPosition>);
 
 #[derive(Default)]
@@ -19,7 +44,26 @@ pub struct MobPositions {
     pub positions: Vec<(MapPosition, Entity)>,
 }
 
+/// How far rendering is between the last simulation tick and the next one, from
+/// 0 to 1. Moving entities are drawn that much further along.
+#[derive(Clone, Copy, Default)]
+pub struct TickAlpha(pub f32);
+
 pub struct AudioHandler {
-    pub astream_handle: rodio::OutputStreamHandle,
-    pub hit_sounds: HashMap<&'static str, Vec<u8>>,
+    pub backend: Box<dyn AudioBackend>,
+    // simulation tick that played sounds are stamped with
+    pub tick: u64,
+}
+
+impl AudioHandler {
+    pub fn new(backend: impl AudioBackend + 'static) -> Self {
+        AudioHandler {
+            backend: Box::new(backend),
+            tick: 0,
+        }
+    }
+
+    pub fn silent() -> Self {
+        AudioHandler::new(NullAudio)
+    }
 }
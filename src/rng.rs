@@ -1,6 +1,20 @@
 use std::ops::Range;
 
 use pcg_mwc::Mwc256XXA64;
+use rand::SeedableRng;
+
+/// Seed a run was started with, kept so it can be shown and shared.
+pub struct RunSeed(pub String);
+
+/// Builds the game RNG from either a plain `u64` or the JSON serialized RNG
+/// state that gets logged at startup.
+pub fn rng_from_seed(seed: &str) -> Result<Mwc256XXA64, serde_json::Error> {
+    let seed = seed.trim();
+    match seed.parse::<u64>() {
+        Ok(n) => Ok(Mwc256XXA64::seed_from_u64(n)),
+        Err(_) => serde_json::from_str(seed),
+    }
+}
 
 #[derive(PartialEq)]
 pub enum CoinFlip { Heads, Tails}
@@ -0,0 +1,174 @@
+use std::{io, path::Path};
+
+use legion::{
+    serialize::{Canon, EntityName, Registry},
+    *,
+};
+use log::warn;
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
+
+use crate::{
+    components::*,
+    dungeon::Dungeon,
+    game::{GameState, TheRng},
+    map::{Map, MapViewport},
+    resources::{LevelStats, PlayerPosition},
+    rng::RunSeed,
+    simulation::Simulation,
+    systems::update_text_info_system,
+};
+
+// 2 saves monsters by their id in monsters.json
+const SAVE_FORMAT_VERSION: u32 = 2;
+
+// everything outside the legion world that makes up a game in progress
+#[derive(Serialize, Deserialize)]
+struct SaveHeader {
+    format_version: u32,
+    game_version: String,
+    seed: Option<String>,
+    tick: u64,
+    game_state: GameState,
+    level_stats: LevelStats,
+    rng: TheRng,
+    map: Option<Map>,
+    viewport: Option<MapViewport>,
+    player_position: PlayerPosition,
+    player: EntityName,
+    dungeon: Dungeon,
+}
+
+// the HUD TextBlocks aren't saved, they get rebuilt on load
+fn registry() -> Registry<String> {
+    let mut registry = Registry::new();
+
+    macro_rules! register {
+        ($($component:ty),* $(,)?) => {
+            $(registry.register::<$component>(stringify!($component).to_owned());)*
+        };
+    }
+
+    register!(
+        EntityType,
+        MapPosition,
+        ScreenDrawOffset,
+        ActionState,
+        RenderableSprite,
+        AnimationState,
+        PlayerFlag,
+        ProjectileFlag,
+        EnemyFlag,
+        PlayerViewportFlag,
+        OnlyVisibleInPlayerFOV,
+        UpdateViewshedsFlag,
+        Moving,
+        Viewshed,
+        TimeToLive,
+        DealDamage,
+        Score,
+        Health,
+        Ammo,
+        MoveTimer,
+        HazardTimer,
+    );
+
+    registry
+}
+
+/// Serializes the full game state. The result is two lines of JSON: the
+/// resources, followed by the legion world.
+pub fn save_game(sim: &Simulation) -> Result<String, serde_json::Error> {
+    let registry = registry();
+    let canon = Canon::default();
+
+    let world = serde_json::to_string(&sim.world.as_serializable(
+        !component::<TextBlock>(),
+        &registry,
+        &canon,
+    ))?;
+
+    let header = SaveHeader {
+        format_version: SAVE_FORMAT_VERSION,
+        game_version: env!("CARGO_PKG_VERSION").to_owned(),
+        seed: sim.resources.get::<RunSeed>().map(|s| s.0.clone()),
+        tick: sim.tick,
+        game_state: sim.game_state(),
+        level_stats: sim.resources.get::<LevelStats>().unwrap().clone(),
+        rng: sim.resources.get::<TheRng>().unwrap().clone(),
+        map: sim.resources.get::<Map>().map(|m| m.clone()),
+        viewport: sim.resources.get::<MapViewport>().map(|v| v.clone()),
+        player_position: sim.resources.get::<PlayerPosition>().unwrap().clone(),
+        player: canon.canonize_id(sim.entities.player),
+        dungeon: sim.resources.get::<Dungeon>().unwrap().clone(),
+    };
+    let header = serde_json::to_string(&header)?;
+
+    Ok(format!("{}\n{}\n", header, world))
+}
+
+/// Restores a game written by `save_game`. The loaded simulation continues
+/// exactly like the one that was saved.
+pub fn load_game(data: &str) -> Result<Simulation, serde_json::Error> {
+    use serde::de::Error;
+
+    let (header, world) = data
+        .split_once('\n')
+        .ok_or_else(|| serde_json::Error::custom("missing world"))?;
+
+    let header: SaveHeader = serde_json::from_str(header)?;
+    if header.format_version != SAVE_FORMAT_VERSION {
+        return Err(serde_json::Error::custom("unsupported save format version"));
+    }
+    if header.game_version != env!("CARGO_PKG_VERSION") {
+        warn!(
+            "save written by version {}, running {}",
+            header.game_version,
+            env!("CARGO_PKG_VERSION")
+        );
+    }
+
+    let registry = registry();
+    let canon = Canon::default();
+    let world = registry
+        .as_deserialize(&canon)
+        .deserialize(&mut serde_json::Deserializer::from_str(world))?;
+    let player = canon
+        .get_id(&header.player)
+        .ok_or_else(|| serde_json::Error::custom("missing player entity"))?;
+
+    let mut resources = Resources::default();
+    resources.insert(header.game_state);
+    resources.insert(header.level_stats);
+    resources.insert(header.rng);
+    resources.insert(header.player_position);
+    resources.insert(header.dungeon);
+    if let Some(seed) = header.seed {
+        resources.insert(RunSeed(seed));
+    }
+
+    let has_map = header.map.is_some();
+    if let Some(map) = header.map {
+        resources.insert(map);
+    }
+    if let Some(viewport) = header.viewport {
+        resources.insert(viewport);
+    }
+
+    let mut sim = Simulation::from_world(world, resources, player);
+    sim.tick = header.tick;
+
+    if has_map {
+        update_text_info_system(&mut sim);
+    }
+
+    Ok(sim)
+}
+
+pub fn save_to_file(sim: &Simulation, path: impl AsRef<Path>) -> io::Result<()> {
+    std::fs::write(path, save_game(sim)?)
+}
+
+pub fn load_from_file(path: impl AsRef<Path>) -> io::Result<Simulation> {
+    let data = std::fs::read_to_string(path)?;
+    Ok(load_game(&data)?)
+}
@@ -47,17 +47,15 @@ impl DrawTarget for FrameBufferTarget {
     where
         I: IntoIterator<Item = Pixel<Self::Color>>,
     {
-        for Pixel(coord, color) in pixels.into_iter() {
-            if let Ok(Point{x, y}) = coord.try_into() {
-                let idx = (x + y * self.width as i32) as usize;
-                if idx < self.temp_buffer.len() {
-                    let val = (color.r() as u32) << 24 |
-                        (color.g() as u32) << 16 |
-                        (color.b() as u32) << 8 |
-                        0xff;
-
-                    self.temp_buffer[idx] = val;
-                }
+        for Pixel(Point{x, y}, color) in pixels.into_iter() {
+            let idx = (x + y * self.width as i32) as usize;
+            if idx < self.temp_buffer.len() {
+                let val = (color.r() as u32) << 24 |
+                    (color.g() as u32) << 16 |
+                    (color.b() as u32) << 8 |
+                    0xff;
+
+                self.temp_buffer[idx] = val;
             }
         }
 
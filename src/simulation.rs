@@ -0,0 +1,211 @@
+use embedded_graphics::text::Alignment;
+use legion::{systems::Resource, *};
+
+use crate::animations::Animations;
+use crate::components::*;
+use crate::dungeon::Dungeon;
+use crate::events::GameEvents;
+use crate::game::{EntityContainer, GameState, TheRng};
+use crate::input::TickInput;
+use crate::map::{Map, MapViewport};
+use crate::mapgen::LevelProgression;
+use crate::monsters::Monsters;
+use crate::resources::*;
+use crate::rng::{rng_from_seed, RunSeed};
+use crate::tiled::LevelList;
+use crate::update::{self, UpdateResult};
+use crate::ScheduleBag;
+
+/// The game logic without a window, GPU surface or audio device. Frontends
+/// feed it one `TickInput` per tick and draw whatever it leaves in `world`.
+pub struct Simulation {
+    pub world: World,
+    pub resources: Resources,
+
+    pub schedule_bag: ScheduleBag,
+
+    pub entities: EntityContainer,
+
+    // number of completed calls to step
+    pub tick: u64,
+}
+
+impl Simulation {
+    pub fn new(rng: TheRng) -> Self {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+
+        let player_entity = world.push((
+            PlayerFlag,
+            AnimationState::default(),
+            RenderableSprite::default(),
+            ActionState(types::Action::Stationary),
+            MapPosition { x: 1, y: 1 },
+            Viewshed {
+                visible: Vec::new(),
+                range: 4,
+            },
+            Health(10),
+            Score(0),
+        ));
+
+        world
+            .entry(player_entity)
+            .unwrap()
+            .add_component(EntityType::Player);
+
+        world.entry(player_entity).unwrap().add_component(Ammo(10));
+
+        world.push((PlayerViewportFlag,));
+        world.push((UpdateViewshedsFlag,));
+
+        let level_stats = LevelStats {
+            level: 1,
+            ..LevelStats::default()
+        };
+
+        resources.insert(PlayerPosition::default());
+        resources.insert(level_stats);
+        resources.insert(Dungeon::default());
+        resources.insert(rng);
+        resources.insert(GameState::Init);
+
+        Simulation::from_world(world, resources, player_entity)
+    }
+
+    /// Wraps a populated world, adding the HUD entities and the resources
+    /// that aren't part of the game state.
+    pub(crate) fn from_world(
+        mut world: World,
+        mut resources: Resources,
+        player_entity: Entity,
+    ) -> Self {
+        let level_info_entity = world.push((
+            TextBlock {
+                text: "Level: 1".to_owned(),
+                color: (0, 210, 0),
+                alignment: Alignment::Left,
+            },
+            FixedScreenPos { x: 5, y: 5 },
+        ));
+
+        let score_info_entity = world.push((
+            TextBlock {
+                text: "Score: 0".to_owned(),
+                color: (0, 210, 0),
+                alignment: Alignment::Left,
+            },
+            FixedScreenPos { x: 5, y: 5 + 8 },
+        ));
+
+        let remaining_info_entity = world.push((
+            TextBlock {
+                text: "Remaining: ??".to_owned(),
+                color: (0, 210, 0),
+                alignment: Alignment::Left,
+            },
+            FixedScreenPos { x: 5 + 100, y: 5 },
+        ));
+
+        let health_info_entity = world.push((
+            TextBlock {
+                text: "Health:".to_owned(),
+                color: (0, 210, 0),
+                alignment: Alignment::Left,
+            },
+            FixedScreenPos {
+                x: 5 + 100 + 100,
+                y: 5,
+            },
+        ));
+
+        let ammo_info_entity = world.push((
+            TextBlock {
+                text: "Ammo:".to_owned(),
+                color: (0, 210, 0),
+                alignment: Alignment::Left,
+            },
+            FixedScreenPos {
+                x: 5 + 100 + 100,
+                y: 5 + 8,
+            },
+        ));
+
+        let entities = EntityContainer {
+            player: player_entity,
+            level: level_info_entity,
+            health: health_info_entity,
+            ammo: ammo_info_entity,
+            remaining: remaining_info_entity,
+            score: score_info_entity,
+        };
+
+        resources.insert(PlayerEntity(player_entity));
+        resources.insert(MobPositions::default());
+        resources.insert(AudioHandler::silent());
+        resources.insert(GameEvents::default());
+        resources.insert(LevelProgression::default());
+        resources.insert(LevelList::default());
+        resources.insert(Monsters::default());
+        resources.insert(Animations::default());
+
+        Simulation {
+            world,
+            resources,
+            entities,
+            schedule_bag: ScheduleBag::default(),
+            tick: 0,
+        }
+    }
+
+    /// Starts a run from a seed as accepted by `rng_from_seed`.
+    pub fn from_seed(seed: &str) -> Result<Self, serde_json::Error> {
+        let mut sim = Simulation::new(rng_from_seed(seed)?);
+        sim.resources.insert(RunSeed(seed.trim().to_owned()));
+
+        Ok(sim)
+    }
+
+    /// Throws away the run in progress for a new one from `seed`. The loaded
+    /// data and whatever the frontend added to `resources` are kept.
+    pub fn restart(&mut self, seed: &str) -> Result<(), serde_json::Error> {
+        let mut fresh = Simulation::from_seed(seed)?;
+
+        fn carry<T: Resource>(from: &mut Resources, to: &mut Resources) {
+            if let Some(resource) = from.remove::<T>() {
+                to.insert(resource);
+            }
+        }
+
+        let (from, to) = (&mut fresh.resources, &mut self.resources);
+        carry::<GameState>(from, to);
+        carry::<TheRng>(from, to);
+        carry::<RunSeed>(from, to);
+        carry::<LevelStats>(from, to);
+        carry::<Dungeon>(from, to);
+        carry::<PlayerEntity>(from, to);
+        carry::<PlayerPosition>(from, to);
+        carry::<MobPositions>(from, to);
+        carry::<GameEvents>(from, to);
+        to.remove::<Map>();
+        to.remove::<MapViewport>();
+
+        self.world = fresh.world;
+        self.entities = fresh.entities;
+
+        Ok(())
+    }
+
+    pub fn step(&mut self, input: &TickInput) -> UpdateResult {
+        self.resources.get_mut::<AudioHandler>().unwrap().tick = self.tick;
+
+        let result = update::do_update(self, input);
+        self.tick += 1;
+
+        result
+    }
+
+    pub fn game_state(&self) -> GameState {
+        *self.resources.get::<GameState>().unwrap()
+    }
+}
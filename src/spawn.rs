@@ -1,9 +1,16 @@
+use std::fmt;
+
 use legion::*;
-use num_traits::Signed;
+use log::warn;
 
 use crate::rng::Rng;
 
-use super::{components::*, game::TheRng, map::Map};
+use super::{
+    components::*,
+    game::TheRng,
+    map::{Map, SpawnKind, TileType},
+    monsters::{MonsterId, Monsters},
+};
 
 pub enum EnemyState {
     Stationary,
@@ -37,32 +44,212 @@ fn item_positions(world: &mut World) -> Vec<MapPosition> {
     query
         .iter(world)
         .filter(|&(_, et)| *et == EntityType::Health || *et == EntityType::Ammo)
-        .map(|(pos, _)| pos.clone())
+        .map(|(pos, _)| *pos)
         .collect()
 }
 
-pub fn spawn_items(
+/// Nowhere left to put something.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapFull;
+
+impl fmt::Display for MapFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no free reachable tile left on the map")
+    }
+}
+
+impl std::error::Error for MapFull {}
+
+// Reachable tiles nothing is standing on. On maps made of rooms only the room
+// tiles, other than the room or prefab the player is in when
+// `avoid_player_room` is set, as long as any of those are free.
+fn free_spots(
     world: &mut World,
     map: &Map,
-    rng: &mut TheRng,
     player_pos: &MapPosition,
-    entity_type: EntityType,
-) {
+    avoid_player_room: bool,
+) -> Vec<MapPosition> {
     let mob_positions = mob_positions(world);
     let item_positions = item_positions(world);
-    let player_pos_idx = map.idx(player_pos.x, player_pos.y);
-    let pos: MapPosition;
-    loop {
-        let p = map.random_open_spot(rng);
-        if !mob_positions.contains(&p)
-            && !item_positions.contains(&p)
-            && map.open_path_a_b(map.idx(p.x, p.y), player_pos_idx)
-        {
-            pos = p;
-            break;
-        }
+
+    let free = map
+        .reachable
+        .iter()
+        .map(|&idx| {
+            let (x, y) = map.rev_idx(idx);
+            MapPosition { x, y }
+        })
+        .filter(|p| p != player_pos && !mob_positions.contains(p) && !item_positions.contains(p))
+        .filter(|&p| safe(map, p))
+        .collect::<Vec<_>>();
+
+    let player_room = map.area_at(*player_pos).filter(|_| avoid_player_room);
+    let in_rooms = free
+        .iter()
+        .copied()
+        .filter(|&p| map.room_at(p).is_some() && !player_room.is_some_and(|r| r.contains(p)))
+        .collect::<Vec<_>>();
+
+    if in_rooms.is_empty() {
+        free
+    } else {
+        in_rooms
+    }
+}
+
+// nothing spawns somewhere that hurts, or on stairs
+fn safe(map: &Map, pos: MapPosition) -> bool {
+    let tile = map.cells[map.idx(pos.x, pos.y)];
+    tile.damage() == 0 && tile != TileType::Exit && tile != TileType::StairsUp
+}
+
+fn pick(spots: &[MapPosition], rng: &mut TheRng) -> Result<MapPosition, MapFull> {
+    if spots.is_empty() {
+        return Err(MapFull);
+    }
+
+    Ok(spots[rng.range(0..spots.len() as i32) as usize])
+}
+
+// whether a marked spawn at `pos` follows the same rules as a random one
+fn marker_allowed(
+    world: &mut World,
+    map: &Map,
+    player_pos: &MapPosition,
+    avoid_player_room: bool,
+    pos: MapPosition,
+) -> bool {
+    let in_player_room = map
+        .area_at(*player_pos)
+        .is_some_and(|r| avoid_player_room && r.contains(pos));
+
+    map.is_reachable(pos)
+        && safe(map, pos)
+        && pos != *player_pos
+        && !in_player_room
+        && !mob_positions(world).contains(&pos)
+        && !item_positions(world).contains(&pos)
+}
+
+/// Where the player starts a level: a reachable start marker if there is one,
+/// else in one of the rooms if the map has any. Fails on maps without a
+/// reachable tile.
+pub fn player_start(map: &Map, rng: &mut TheRng) -> Result<MapPosition, MapFull> {
+    if let Some(start) = map
+        .spawns
+        .iter()
+        .find(|s| s.kind == SpawnKind::PlayerStart && map.is_reachable(s.pos))
+    {
+        return Ok(start.pos);
+    }
+
+    let reachable = map
+        .reachable
+        .iter()
+        .map(|&idx| {
+            let (x, y) = map.rev_idx(idx);
+            MapPosition { x, y }
+        })
+        .collect::<Vec<_>>();
+    // anywhere but in lava or on stairs, unless there's nothing else
+    let safe_spots = reachable
+        .iter()
+        .copied()
+        .filter(|&p| safe(map, p))
+        .collect::<Vec<_>>();
+    let reachable = if safe_spots.is_empty() {
+        reachable
+    } else {
+        safe_spots
+    };
+    let in_rooms = reachable
+        .iter()
+        .copied()
+        .filter(|&p| map.room_at(p).is_some())
+        .collect::<Vec<_>>();
+
+    let spots = if in_rooms.is_empty() {
+        reachable
+    } else {
+        in_rooms
+    };
+    pick(&spots, rng)
+}
+
+/// Where to put the exit on a level that doesn't come with one: the plain
+/// floor tile furthest from `start` on foot, so the level lies in between.
+pub fn exit_spot(map: &Map, start: MapPosition) -> Option<MapPosition> {
+    let start = map.idx(start.x, start.y);
+    let distances = map.walking_distances(start);
+
+    map.reachable
+        .iter()
+        .copied()
+        .filter(|&idx| idx != start && map.cells[idx] == TileType::Floor)
+        // the first of the furthest ones
+        .max_by_key(|&idx| (distances[idx], std::cmp::Reverse(idx)))
+        .map(|idx| {
+            let (x, y) = map.rev_idx(idx);
+            MapPosition { x, y }
+        })
+}
+
+/// Where the player comes out when taking the stairs to a level: the tile
+/// closest to the level's `stairs` that isn't `occupied`, or the usual start if
+/// it has none.
+pub fn arrival_spot(
+    map: &Map,
+    rng: &mut TheRng,
+    stairs: TileType,
+    occupied: &[MapPosition],
+) -> Result<MapPosition, MapFull> {
+    let Some(stairs) = map.cells.iter().position(|&t| t == stairs) else {
+        return player_start(map, rng);
+    };
+    let distances = map.walking_distances(stairs);
+
+    let closest = map
+        .reachable
+        .iter()
+        .map(|&idx| {
+            let (x, y) = map.rev_idx(idx);
+            (idx, MapPosition { x, y })
+        })
+        .filter(|&(idx, p)| idx != stairs && safe(map, p) && !occupied.contains(&p))
+        .filter_map(|(idx, p)| distances[idx].map(|d| (d, idx, p)))
+        .min_by_key(|&(d, idx, _)| (d, idx))
+        .map(|(_, _, p)| p);
+
+    match closest {
+        Some(pos) => Ok(pos),
+        None => player_start(map, rng),
     }
+}
+
+pub fn spawn_items(
+    world: &mut World,
+    map: &Map,
+    rng: &mut TheRng,
+    player_pos: &MapPosition,
+    entity_type: EntityType,
+) -> Result<Entity, MapFull> {
+    let pos = pick(&free_spots(world, map, player_pos, false), rng)?;
+
+    Ok(push_item(world, entity_type, pos))
+}
 
+/// Spawns an item on a prefab marker, if the spot is free and reachable.
+pub fn spawn_item_at(
+    world: &mut World,
+    map: &Map,
+    player_pos: &MapPosition,
+    entity_type: EntityType,
+    pos: MapPosition,
+) -> Option<Entity> {
+    marker_allowed(world, map, player_pos, false, pos).then(|| push_item(world, entity_type, pos))
+}
+
+pub(crate) fn push_item(world: &mut World, entity_type: EntityType, pos: MapPosition) -> Entity {
     world.push((
         entity_type,
         OnlyVisibleInPlayerFOV,
@@ -70,7 +257,7 @@ pub fn spawn_items(
         AnimationState::default(),
         ActionState(types::Action::Stationary),
         pos,
-    ));
+    ))
 }
 
 pub fn spawn_enemy(
@@ -79,37 +266,46 @@ pub fn spawn_enemy(
     rng: &mut TheRng,
     player_pos: &MapPosition,
     level: i32,
-) {
-    let current_positions = mob_positions(world);
-    let item_positions = item_positions(world);
-    let player_pos_idx = map.idx(player_pos.x, player_pos.y);
-    let pos: MapPosition;
-    loop {
-        let p = map.random_open_spot(rng);
-        if !current_positions.contains(&p)
-            && p != *player_pos
-            && !item_positions.contains(&p)
-            && map.open_path_a_b(map.idx(p.x, p.y), player_pos_idx)
-        {
-            pos = p;
-            break;
-        }
-    }
+    monsters: &Monsters,
+) -> Result<Entity, MapFull> {
+    let pos = pick(&free_spots(world, map, player_pos, true), rng)?;
 
-    let roll = rng.d100();
-    let monter_type = if roll > 50 {
-        EntityType::MonsterA
-    } else if roll > 20 {
-        EntityType::MonsterB
-    } else {
-        EntityType::MonsterC
-    };
+    Ok(push_enemy(world, rng, monsters, level, pos, None))
+}
 
-    let health = match monter_type {
-        EntityType::MonsterA => 1,
-        EntityType::MonsterB => 2,
-        EntityType::MonsterC => 3,
-        _ => unreachable!(),
+/// Spawns an enemy on a prefab marker, if the spot is free, reachable and
+/// outside the player's room. `monster_type` of `None` is rolled as usual.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_enemy_at(
+    world: &mut World,
+    map: &Map,
+    rng: &mut TheRng,
+    player_pos: &MapPosition,
+    level: i32,
+    monsters: &Monsters,
+    pos: MapPosition,
+    monster_type: Option<MonsterId>,
+) -> Option<Entity> {
+    marker_allowed(world, map, player_pos, true, pos)
+        .then(|| push_enemy(world, rng, monsters, level, pos, monster_type))
+}
+
+/// Unknown monsters are rolled like `None`.
+pub(crate) fn push_enemy(
+    world: &mut World,
+    rng: &mut TheRng,
+    monsters: &Monsters,
+    level: i32,
+    pos: MapPosition,
+    monster_type: Option<MonsterId>,
+) -> Entity {
+    let def = match monster_type.map(|id| (id, monsters.get(id))) {
+        Some((_, Some(def))) => def,
+        Some((id, None)) => {
+            warn!("unknown monster {}, rolling one instead", id);
+            monsters.roll(level, rng)
+        }
+        None => monsters.roll(level, rng),
     };
 
     let entity = world.push((
@@ -121,23 +317,19 @@ pub fn spawn_enemy(
         pos,
         Viewshed {
             visible: Vec::new(),
-            range: level.clamp(8, 50),
+            range: def.sight_range(level),
         },
-        Health(health),
+        Health(def.health),
     ));
 
     world.entry(entity).unwrap().add_component(MoveTimer {
-        time_left: get_move_speed_for_mob(monter_type, rng),
+        time_left: def.speed.roll(rng),
     });
 
-    world.entry(entity).unwrap().add_component(monter_type);
-}
+    world
+        .entry(entity)
+        .unwrap()
+        .add_component(EntityType::Monster(def.id));
 
-pub fn get_move_speed_for_mob(entity_type: EntityType, rng: &mut TheRng) -> i32 {
-    match entity_type {
-        EntityType::MonsterA => rng.d10() as i32 * 10,
-        EntityType::MonsterB => rng.d10() as i32 * 25,
-        EntityType::MonsterC => rng.d10() as i32 * 40,
-        _ => unreachable!(),
-    }
+    entity
 }
@@ -13,7 +13,7 @@ where
     grid_data: Vec<Vec<P>>,
 }
 
-impl <'a, P: Pixel, Container> SpriteGrid<P, Container>
+impl <P: Pixel, Container> SpriteGrid<P, Container>
 where
     Container: Deref<Target = [P::Subpixel]>
 {
@@ -36,6 +36,11 @@ where
         grid
     }
 
+    /// Width and height of a single sprite.
+    pub fn cell_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
     pub fn idx(&self, grid_x: u32, grid_y: u32) -> usize {
         (grid_y * self.num_cols + grid_x) as usize
     }
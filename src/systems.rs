@@ -1,24 +1,25 @@
 use std::collections::HashMap;
 
+use crate::animations::Animations;
 use crate::components::*;
 use crate::data::*;
+use crate::events::{GameEvent, GameEvents};
 use crate::game::play_sound;
-use crate::game::CoreGame;
 use crate::game::TheRng;
 use crate::map::Map;
 use crate::map::MapViewport;
+use crate::monsters::Monsters;
 use crate::render::*;
 use crate::resources::*;
 use crate::rng::CoinFlip;
 use crate::rng::Rng;
-use crate::spawn::get_move_speed_for_mob;
+use crate::simulation::Simulation;
 use crate::sprites::get_renderable;
 use crate::utils::delta_to_next_map_position;
 use crate::utils::effective_moving_map_position;
 
 use bracket_pathfinding::prelude::{a_star_search, field_of_view, Point};
 use legion::*;
-use num_traits::Signed;
 use systems::CommandBuffer;
 use types::DeltaPosition;
 use world::SubWorld;
@@ -28,6 +29,7 @@ pub enum ScheduleName {
     RenderRunning,
     RenderNewMapWait,
     UpdateRunning,
+    Events,
 }
 
 pub struct ScheduleBag {
@@ -70,12 +72,22 @@ impl Default for ScheduleBag {
                 .add_thread_local(update_enemy_movement_system())
                 .flush()
                 .add_thread_local(projectile_collision_system())
+                .add_thread_local(hazard_damage_system())
                 // keyboard input should go here
                 .add_thread_local(deal_damage_system())
                 .add_thread_local(update_time_to_live_system())
                 .build(),
         );
 
+        schedules.insert(
+            Events,
+            Schedule::builder()
+                .add_thread_local(play_event_sounds_system())
+                .add_thread_local(apply_pickups_system())
+                .add_thread_local(award_kills_system())
+                .build(),
+        );
+
         ScheduleBag { schedules }
     }
 }
@@ -115,12 +127,22 @@ impl Default for ScheduleBag {
                 .add_system(update_enemy_movement_system())
                 .flush()
                 .add_system(projectile_collision_system())
+                .add_system(hazard_damage_system())
                 // keyboard input should go here
                 .add_system(deal_damage_system())
                 .add_system(update_time_to_live_system())
                 .build(),
         );
 
+        schedules.insert(
+            Events,
+            Schedule::builder()
+                .add_system(play_event_sounds_system())
+                .add_system(apply_pickups_system())
+                .add_system(award_kills_system())
+                .build(),
+        );
+
         ScheduleBag { schedules }
     }
 }
@@ -182,8 +204,9 @@ pub fn update_player_viewport(
         return;
     }
 
-    // update visible cells on map based on player viewshed
-    //map.visible_cells.iter_mut().for_each(|v| *v = false);
+    // update visible cells on map based on player viewshed, anything seen
+    // stays revealed
+    map.visible_cells.iter_mut().for_each(|v| *v = false);
 
     let player_entry = world.entry_mut(player_entity).unwrap();
     let viewshed = player_entry.get_component::<Viewshed>().unwrap();
@@ -191,6 +214,7 @@ pub fn update_player_viewport(
     for &MapPosition { x, y } in viewshed.visible.iter() {
         let idx = map.idx(x, y);
         map.visible_cells[idx] = true;
+        map.revealed_cells[idx] = true;
     }
 
     // map update viewport
@@ -216,6 +240,7 @@ fn update_positions(
     #[resource] &PlayerEntity(player_entity): &PlayerEntity,
     #[resource] player_pos: &mut PlayerPosition,
     #[resource] map: &Map,
+    #[resource] events: &mut GameEvents,
 ) {
     use EntityType::*;
     // gather items
@@ -250,7 +275,9 @@ fn update_positions(
                 *y += dy;
                 buffer.remove_component::<Moving>(entity);
                 buffer.push((UpdateViewshedsFlag,));
-                action_state.map(|s| s.0 = types::Action::Stationary);
+                if let Some(s) = action_state {
+                    s.0 = types::Action::Stationary;
+                }
             }
 
             if entity == player_entity {
@@ -260,30 +287,14 @@ fn update_positions(
 
                     player_pos.1 = None;
 
-                    if let Some(item) = item_positions.get(&map.idx(player_pos.0.x, player_pos.0.y))
+                    if let Some(&(item_entity, item)) =
+                        item_positions.get(&map.idx(player_pos.0.x, player_pos.0.y))
                     {
-                        let entity_type = item.1;
-                        buffer.exec_mut(move |world, resources| {
-                            let player_entity = resources.get::<PlayerEntity>().unwrap();
-                            let mut player_entry = world.entry(player_entity.0).unwrap();
-                            match entity_type {
-                                Ammo => {
-                                    let ammo = player_entry
-                                        .get_component_mut::<crate::components::Ammo>()
-                                        .unwrap();
-                                    ammo.0 = 10.min(ammo.0 + 1);
-                                }
-                                Health => {
-                                    let health = player_entry
-                                        .get_component_mut::<crate::components::Health>()
-                                        .unwrap();
-                                    health.0 = 10.min(health.0 + 1);
-                                }
-                                _ => unreachable!(),
-                            }
+                        events.emit(GameEvent::ItemPickedUp {
+                            item,
+                            pos: player_pos.0,
                         });
-
-                        buffer.remove(item.0);
+                        buffer.remove(item_entity);
                     }
 
                     buffer.push((PlayerViewportFlag,));
@@ -300,7 +311,7 @@ fn update_positions(
 #[read_component(ActionState)]
 #[write_component(AnimationState)]
 #[write_component(RenderableSprite)]
-fn update_renderable(world: &mut SubWorld) {
+fn update_renderable(world: &mut SubWorld, #[resource] animations: &Animations) {
     let mut query = <(
         &EntityType,
         &ActionState,
@@ -308,10 +319,11 @@ fn update_renderable(world: &mut SubWorld) {
         &mut RenderableSprite,
     )>::query();
     query.for_each_mut(world, |(etype, act, anim, r)| {
-        let rend = get_renderable(etype, *act, anim, r.facing);
+        let rend = get_renderable(etype, *act, anim, r.facing, animations);
 
         r.sprite_x = rend.sprite_x;
         r.sprite_y = rend.sprite_y;
+        r.flip = rend.flip;
     });
 }
 
@@ -337,9 +349,12 @@ pub fn update_time_to_live(world: &mut SubWorld, buffer: &mut CommandBuffer) {
 #[read_component(EntityType)]
 #[write_component(MoveTimer)]
 #[write_component(DealDamage)]
+#[allow(clippy::too_many_arguments)]
 pub fn update_enemy_movement(
-    #[resource] map: &Map,
+    #[resource] map: &mut Map,
+    #[resource] events: &mut GameEvents,
     #[resource] rng: &mut TheRng,
+    #[resource] monsters: &Monsters,
     #[resource] PlayerEntity(player_entity): &PlayerEntity,
     #[resource] player_position: &PlayerPosition,
     world: &mut SubWorld,
@@ -369,20 +384,38 @@ pub fn update_enemy_movement(
                 return;
             }
 
-            move_timer.time_left = get_move_speed_for_mob(*entity_type, rng);
+            // monsters that aren't defined (any more) stay where they are
+            let EntityType::Monster(id) = *entity_type else {
+                return;
+            };
+            let Some(def) = monsters.get(id) else {
+                return;
+            };
+            move_timer.time_left = def.speed.roll(rng);
 
             if viewshed.visible.contains(&MapPosition {
                 x: player_world_pos.x,
                 y: player_world_pos.y,
             }) {
                 let start = map.idx(x, y);
-                let path = a_star_search(start, player_pos_idx, map);
+                let path = a_star_search(start, player_pos_idx, &*map);
                 if path.success {
                     if path.steps.len() < 10 && path.steps.len() > 2 {
                         // move
                         let next_step_idx = path.steps[1];
-                        // make sure not moving into next player move position
-                        if player_pos_next_idx.is_none_or(|idx| idx != next_step_idx) {
+                        let next_tile = map.cells[next_step_idx];
+                        if let Some(opened) = next_tile.opened() {
+                            // the step goes through a closed door, open it
+                            // instead of moving
+                            map.set_tile(next_step_idx, opened);
+                            let (x, y) = map.rev_idx(next_step_idx);
+                            events.emit(GameEvent::DoorOpened {
+                                pos: MapPosition { x, y },
+                            });
+                            buffer.push((UpdateViewshedsFlag,));
+                            buffer.push((PlayerViewportFlag,));
+                        } else if player_pos_next_idx.is_none_or(|idx| idx != next_step_idx) {
+                            // not moving into the player's next position
                             let delta = match next_step_idx as i32 - start as i32 {
                                 1 => MapPosition { x: 1, y: 0 },
                                 -1 => MapPosition { x: -1, y: 0 },
@@ -390,20 +423,21 @@ pub fn update_enemy_movement(
                                 _ => MapPosition { x: 0, y: 1 },
                             };
 
+                            let ticks = PLAYER_MOVE_TICKS as i32 * next_tile.move_ticks_factor();
                             let delta_f = DeltaPosition {
-                                x: delta.x as f32 / PLAYER_MOVE_TICKS as f32,
-                                y: delta.y as f32 / PLAYER_MOVE_TICKS as f32,
+                                x: delta.x as f32 / ticks as f32,
+                                y: delta.y as f32 / ticks as f32,
                             };
                             let mover = Moving {
-                                ticks_left: PLAYER_MOVE_TICKS as i32,
-                                total_ticks: PLAYER_MOVE_TICKS as i32,
+                                ticks_left: ticks,
+                                total_ticks: ticks,
                                 delta: delta_f,
                             };
                             to_move.push((entity, mover, next_step_idx));
                         }
                     } else if path.steps.len() == 2 {
                         // attack
-                        to_attack.push(entity);
+                        to_attack.push(def.damage);
                     }
                 }
             }
@@ -421,10 +455,10 @@ pub fn update_enemy_movement(
         }
     }
 
-    for _entity in to_attack {
+    for amount in to_attack {
         let damage = DealDamage {
             target: *player_entity,
-            amount: 1,
+            amount,
         };
 
         buffer.push((damage,));
@@ -434,11 +468,13 @@ pub fn update_enemy_movement(
 #[system]
 #[read_component(Entity)]
 #[read_component(DealDamage)]
+#[read_component(EntityType)]
 #[write_component(Health)]
 pub fn deal_damage(
     world: &mut SubWorld,
     buffer: &mut CommandBuffer,
-    #[resource] audio: &mut AudioHandler,
+    #[resource] &PlayerEntity(player_entity): &PlayerEntity,
+    #[resource] events: &mut GameEvents,
 ) {
     let mut dmg = Vec::new();
 
@@ -455,7 +491,19 @@ pub fn deal_damage(
             health.0 = health.0.max(0);
             let current_health = health.0;
 
-            play_sound("hit1", audio);
+            if target == player_entity {
+                events.emit(GameEvent::PlayerDamaged {
+                    amount,
+                    health: current_health,
+                });
+            } else if let Ok(&EntityType::Monster(monster)) = entry.get_component::<EntityType>() {
+                events.emit(GameEvent::EnemyDamaged {
+                    entity: target,
+                    monster,
+                    amount,
+                    health: current_health,
+                });
+            }
 
             if current_health >= 0 {
                 //let pos = entry.get_component::<PosF>().unwrap();
@@ -495,29 +543,30 @@ fn spawn_item(world: &mut World, entity_type: EntityType, pos: MapPosition) {
     ));
 }
 
-pub fn dead_ememy_remover_system(game: &mut CoreGame) -> bool {
-    let mut query = <(Entity, &Health, &MapPosition)>::query();
+pub fn dead_ememy_remover_system(game: &mut Simulation) -> bool {
+    let mut query = <(Entity, &Health, &MapPosition, Option<&EntityType>)>::query();
     let mut removers = Vec::new();
     let mut spawn = None;
-    let mut score_mod = 0;
     let mut player_dead = false;
-    for (entity, &Health(h), pos) in query.iter_mut(&mut game.world) {
+    for (entity, &Health(h), pos, entity_type) in query.iter_mut(&mut game.world) {
         if h <= 0 {
             if *entity == game.entities.player {
                 // player dead
                 player_dead = true;
             } else {
-                removers.push((*entity, *pos));
-
-                score_mod += 1;
+                let monster = match entity_type {
+                    Some(&EntityType::Monster(monster)) => Some(monster),
+                    _ => None,
+                };
+                removers.push((*entity, monster, *pos));
 
                 // random drop
                 let mut rng = game.resources.get_mut::<TheRng>().unwrap();
                 if rng.d100() <= 75 {
                     if rng.coin_flip() == CoinFlip::Heads {
-                        spawn = Some((EntityType::Ammo, pos.clone()));
+                        spawn = Some((EntityType::Ammo, *pos));
                     } else {
-                        spawn = Some((EntityType::Health, pos.clone()));
+                        spawn = Some((EntityType::Health, *pos));
                     }
                 }
             }
@@ -528,10 +577,19 @@ pub fn dead_ememy_remover_system(game: &mut CoreGame) -> bool {
         spawn_item(&mut game.world, entity_type, pos);
     }
 
-    for (entity, pos) in removers {
+    for (entity, monster, pos) in removers {
         game.world.remove(entity);
 
-        play_sound("explode1", &game.resources.get::<AudioHandler>().unwrap());
+        if let Some(monster) = monster {
+            game.resources
+                .get_mut::<GameEvents>()
+                .unwrap()
+                .emit(GameEvent::EnemyKilled {
+                    entity,
+                    monster,
+                    pos,
+                });
+        }
 
         let explosion = (
             pos,
@@ -545,24 +603,107 @@ pub fn dead_ememy_remover_system(game: &mut CoreGame) -> bool {
                 sprite_x: 5,
                 sprite_y: 2,
                 facing: types::Facing::None,
+                flip: false,
             },
             TimeToLive(30),
         );
         game.world.push(explosion);
     }
 
-    if score_mod > 0 {
-        let mut query = <(&PlayerFlag, &mut Score, &Health, &mut Viewshed)>::query();
-        for (_player_flag, Score(score), &Health(health), vs) in query.iter_mut(&mut game.world) {
-            *score += score_mod;
-            vs.range = health.clamp(2, 8);
+    player_dead
+}
+
+/// Hands the tick's events to the subscriber systems.
+pub fn dispatch_events_system(game: &mut Simulation) {
+    let schedule = game
+        .schedule_bag
+        .schedules
+        .get_mut(&ScheduleName::Events)
+        .unwrap();
+    schedule.execute(&mut game.world, &mut game.resources);
+}
+
+#[system]
+pub fn play_event_sounds(
+    #[resource] events: &GameEvents,
+    #[resource] audio: &AudioHandler,
+    #[resource] monsters: &Monsters,
+) {
+    use GameEvent::*;
+
+    for event in events.iter() {
+        let sound = match event {
+            EnemyKilled { monster, .. } => monsters
+                .get(*monster)
+                .map_or("explode1", |def| &def.sounds.death),
+            EnemyDamaged { monster, .. } => monsters
+                .get(*monster)
+                .map_or("hit1", |def| &def.sounds.hurt),
+            PlayerDamaged { .. } => "hit1",
+            ProjectileHit { .. } => "hit2",
+            WallBumped { .. } | DoorOpened { .. } => "hit3",
+            WallDestroyed { .. } => "explode1",
+            ShotFired { .. } => "fire1",
+            ItemPickedUp { .. }
+            | LevelCleared { .. }
+            | ExitReached { .. }
+            | StairsUpReached { .. } => continue,
+        };
+
+        play_sound(sound, audio);
+    }
+}
+
+#[system]
+#[write_component(Ammo)]
+#[write_component(Health)]
+pub fn apply_pickups(
+    world: &mut SubWorld,
+    #[resource] events: &GameEvents,
+    #[resource] &PlayerEntity(player_entity): &PlayerEntity,
+) {
+    for event in events.iter() {
+        let &GameEvent::ItemPickedUp { item, .. } = event else {
+            continue;
+        };
+
+        let mut player_entry = world.entry_mut(player_entity).unwrap();
+        match item {
+            EntityType::Ammo => {
+                let ammo = player_entry.get_component_mut::<Ammo>().unwrap();
+                ammo.0 = 10.min(ammo.0 + 1);
+            }
+            EntityType::Health => {
+                let health = player_entry.get_component_mut::<Health>().unwrap();
+                health.0 = 10.min(health.0 + 1);
+            }
+            _ => unreachable!(),
         }
     }
+}
 
-    player_dead
+#[system]
+#[read_component(PlayerFlag)]
+#[read_component(Health)]
+#[write_component(Score)]
+#[write_component(Viewshed)]
+pub fn award_kills(world: &mut SubWorld, #[resource] events: &GameEvents) {
+    let kills = events
+        .iter()
+        .filter(|e| matches!(e, GameEvent::EnemyKilled { .. }))
+        .count() as i32;
+    if kills == 0 {
+        return;
+    }
+
+    let mut query = <(&PlayerFlag, &mut Score, &Health, &mut Viewshed)>::query();
+    for (_player_flag, Score(score), &Health(health), vs) in query.iter_mut(world) {
+        *score += kills;
+        vs.range = health.clamp(2, 8);
+    }
 }
 
-pub fn update_text_info_system(game: &mut CoreGame) {
+pub fn update_text_info_system(game: &mut Simulation) {
     let mut query = <(&PlayerFlag, &Score, &Health, &Ammo)>::query();
     let mut score = 0;
     let mut health = 0;
@@ -608,7 +749,7 @@ pub fn update_text_info_system(game: &mut CoreGame) {
     text.text.push_str(&level.to_string());
 }
 
-pub fn check_map_complete_system(game: &mut CoreGame) -> bool {
+pub fn check_map_complete_system(game: &mut Simulation) -> bool {
     let mut query = <(&EntityType, Option<&EnemyFlag>)>::query();
     query
         .iter(&game.world)
@@ -639,8 +780,8 @@ fn gather_mob_positions(world: &mut SubWorld, #[resource] mob_positions: &mut Mo
 #[read_component(ScreenDrawOffset)]
 fn projectile_collision(
     #[resource] mob_positions: &MobPositions,
-    #[resource] map: &Map,
-    #[resource] audio: &AudioHandler,
+    #[resource] map: &mut Map,
+    #[resource] events: &mut GameEvents,
     world: &mut SubWorld,
     buffer: &mut CommandBuffer,
 ) {
@@ -651,6 +792,14 @@ fn projectile_collision(
         // check for collision with wall
         let idx = map.idx(projectile_position.x, projectile_position.y);
         if map.blocks_movement[idx] {
+            if let Some(rubble) = map.cells[idx].destroyed() {
+                map.set_tile(idx, rubble);
+                events.emit(GameEvent::WallDestroyed {
+                    pos: projectile_position,
+                });
+                buffer.push((UpdateViewshedsFlag,));
+                buffer.push((PlayerViewportFlag,));
+            }
             buffer.remove(*entity);
             return;
         }
@@ -665,10 +814,53 @@ fn projectile_collision(
                     amount: 1,
                 };
 
-                play_sound("hit2", audio);
+                events.emit(GameEvent::ProjectileHit {
+                    target: *mentity,
+                    pos: *p,
+                });
 
                 buffer.push((damage,));
             }
         });
     });
 }
+
+#[system]
+#[read_component(Entity)]
+#[read_component(MapPosition)]
+#[read_component(Health)]
+#[write_component(HazardTimer)]
+fn hazard_damage(world: &mut SubWorld, buffer: &mut CommandBuffer, #[resource] map: &Map) {
+    let mut query =
+        <(Entity, &MapPosition, Option<&mut HazardTimer>)>::query().filter(component::<Health>());
+    query.for_each_mut(world, |(&entity, &MapPosition { x, y }, timer)| {
+        let amount = map.cells[map.idx(x, y)].damage();
+
+        match (amount, timer) {
+            (0, None) => return,
+            (0, Some(_)) => {
+                buffer.remove_component::<HazardTimer>(entity);
+                return;
+            }
+            // hit straight away when stepping on, then every so often
+            (_, None) => buffer.add_component(
+                entity,
+                HazardTimer {
+                    time_left: HAZARD_DAMAGE_TICKS,
+                },
+            ),
+            (_, Some(timer)) => {
+                timer.time_left -= 1;
+                if timer.time_left > 0 {
+                    return;
+                }
+                timer.time_left = HAZARD_DAMAGE_TICKS;
+            }
+        }
+
+        buffer.push((DealDamage {
+            target: entity,
+            amount,
+        },));
+    });
+}
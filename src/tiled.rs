@@ -0,0 +1,468 @@
+//! Levels authored in the Tiled editor, from JSON (`.tmj`, `.json`) or XML
+//! (`.tmx`) maps.
+//!
+//! Tile layers are named after the `TileType` they paint: `Wall`, `Floor`,
+//! `Door` (closed), `OpenDoor`, `Water`, `Lava`, `Rubble`, `CrackedWall`,
+//! `Exit` or `StairsUp`.
+//! Any non-empty tile on such a layer sets that type, later layers win, and
+//! unpainted tiles are wall. The outermost ring is always wall. Layer data has
+//! to be uncompressed: CSV in TMX, a plain array in JSON.
+//!
+//! Objects are told apart by their class (`type` before Tiled 1.9) or, if
+//! that's empty, their name:
+//!
+//! | class                            | places                     |
+//! |----------------------------------|----------------------------|
+//! | `Player`                         | the player start           |
+//! | `Enemy`                          | a monster of a rolled type |
+//! | `Health`, `Ammo`                 | that item                  |
+//! | `Item`                           | a random item              |
+//! | `Room`                           | a room covering the object |
+//! | a monster id, e.g. `MonsterA`    | that monster               |
+//!
+//! Monster ids are looked up in the `Monsters` the map is loaded with. Objects
+//! of any other class are left out with a warning.
+//!
+//! Spawns go on the tile under the object's top left corner.
+
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::Deserialize;
+
+use crate::components::{EntityType, MapPosition};
+use crate::map::{Map, Room, SpawnKind, SpawnPoint, TileType};
+use crate::monsters::{MonsterId, Monsters};
+
+#[derive(Debug)]
+pub enum TiledError {
+    Io(PathBuf, io::Error),
+    Json(serde_json::Error),
+    Xml(roxmltree::Error),
+    Format(String),
+}
+
+impl fmt::Display for TiledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TiledError::Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            TiledError::Json(e) => write!(f, "{}", e),
+            TiledError::Xml(e) => write!(f, "{}", e),
+            TiledError::Format(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for TiledError {}
+
+impl From<serde_json::Error> for TiledError {
+    fn from(e: serde_json::Error) -> Self {
+        TiledError::Json(e)
+    }
+}
+
+impl From<roxmltree::Error> for TiledError {
+    fn from(e: roxmltree::Error) -> Self {
+        TiledError::Xml(e)
+    }
+}
+
+fn format_error(msg: impl Into<String>) -> TiledError {
+    TiledError::Format(msg.into())
+}
+
+// what both file formats boil down to
+struct RawLevel {
+    width: i32,
+    height: i32,
+    tile_width: f32,
+    tile_height: f32,
+    tile_layers: Vec<(String, Vec<u32>)>,
+    objects: Vec<RawObject>,
+}
+
+struct RawObject {
+    class: String,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    // tile objects are anchored at their bottom left corner
+    is_tile: bool,
+}
+
+/// Loads a Tiled map, picking the format from the file extension. Objects can
+/// place any of `monsters`.
+pub fn load_map(path: impl AsRef<Path>, monsters: &Monsters) -> Result<Map, TiledError> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|e| TiledError::Io(path.to_owned(), e))?;
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("tmx") => parse_tmx(&text, monsters),
+        _ => parse_json(&text, monsters),
+    }
+}
+
+pub fn parse_json(json: &str, monsters: &Monsters) -> Result<Map, TiledError> {
+    #[derive(Deserialize)]
+    struct JsonMap {
+        width: i32,
+        height: i32,
+        tilewidth: f32,
+        tileheight: f32,
+        #[serde(default)]
+        infinite: bool,
+        layers: Vec<JsonLayer>,
+    }
+
+    #[derive(Deserialize)]
+    struct JsonLayer {
+        #[serde(rename = "type")]
+        kind: String,
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        encoding: Option<String>,
+        #[serde(default)]
+        data: Option<serde_json::Value>,
+        #[serde(default)]
+        objects: Vec<JsonObject>,
+        #[serde(default)]
+        layers: Vec<JsonLayer>,
+    }
+
+    #[derive(Deserialize)]
+    struct JsonObject {
+        #[serde(default)]
+        name: String,
+        #[serde(default, rename = "type")]
+        kind: String,
+        #[serde(default)]
+        class: String,
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        width: f32,
+        #[serde(default)]
+        height: f32,
+        #[serde(default)]
+        gid: Option<u32>,
+    }
+
+    fn flatten(layers: Vec<JsonLayer>, raw: &mut RawLevel) -> Result<(), TiledError> {
+        for layer in layers {
+            match layer.kind.as_str() {
+                "tilelayer" => {
+                    if layer.encoding.as_deref().is_some_and(|e| e != "csv") {
+                        return Err(format_error(format!(
+                            "layer {}: only uncompressed layer data is supported",
+                            layer.name
+                        )));
+                    }
+                    let data = serde_json::from_value(layer.data.unwrap_or_default())?;
+                    raw.tile_layers.push((layer.name, data));
+                }
+                "objectgroup" => {
+                    for o in layer.objects {
+                        let class = [o.class, o.kind, o.name]
+                            .into_iter()
+                            .find(|c| !c.is_empty())
+                            .unwrap_or_default();
+                        raw.objects.push(RawObject {
+                            class,
+                            x: o.x,
+                            y: o.y,
+                            width: o.width,
+                            height: o.height,
+                            is_tile: o.gid.is_some(),
+                        });
+                    }
+                }
+                "group" => flatten(layer.layers, raw)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    let map: JsonMap = serde_json::from_str(json)?;
+    if map.infinite {
+        return Err(format_error("infinite maps are not supported"));
+    }
+
+    let mut raw = RawLevel {
+        width: map.width,
+        height: map.height,
+        tile_width: map.tilewidth,
+        tile_height: map.tileheight,
+        tile_layers: Vec::new(),
+        objects: Vec::new(),
+    };
+    flatten(map.layers, &mut raw)?;
+
+    build_map(raw, monsters)
+}
+
+pub fn parse_tmx(xml: &str, monsters: &Monsters) -> Result<Map, TiledError> {
+    fn attr<T: std::str::FromStr>(node: roxmltree::Node, name: &str) -> Result<T, TiledError> {
+        node.attribute(name)
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(|| {
+                format_error(format!(
+                    "<{}> needs a valid {}",
+                    node.tag_name().name(),
+                    name
+                ))
+            })
+    }
+
+    fn attr_or<T: std::str::FromStr>(node: roxmltree::Node, name: &str, default: T) -> T {
+        node.attribute(name)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(default)
+    }
+
+    fn flatten(node: roxmltree::Node, raw: &mut RawLevel) -> Result<(), TiledError> {
+        for child in node.children().filter(|c| c.is_element()) {
+            match child.tag_name().name() {
+                "layer" => {
+                    let name = child.attribute("name").unwrap_or_default().to_owned();
+                    let data = child
+                        .children()
+                        .find(|c| c.has_tag_name("data"))
+                        .ok_or_else(|| format_error(format!("layer {} has no data", name)))?;
+                    if data.attribute("encoding") != Some("csv")
+                        || data.has_attribute("compression")
+                    {
+                        return Err(format_error(format!(
+                            "layer {}: only CSV layer data is supported",
+                            name
+                        )));
+                    }
+
+                    let tiles = data
+                        .text()
+                        .unwrap_or_default()
+                        .split(',')
+                        .map(|t| t.trim().parse::<u32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|e| format_error(format!("layer {}: {}", name, e)))?;
+                    raw.tile_layers.push((name, tiles));
+                }
+                "objectgroup" => {
+                    for o in child.children().filter(|c| c.has_tag_name("object")) {
+                        let class = ["class", "type", "name"]
+                            .into_iter()
+                            .filter_map(|a| o.attribute(a))
+                            .find(|c| !c.is_empty())
+                            .unwrap_or_default()
+                            .to_owned();
+                        raw.objects.push(RawObject {
+                            class,
+                            x: attr(o, "x")?,
+                            y: attr(o, "y")?,
+                            width: attr_or(o, "width", 0.0),
+                            height: attr_or(o, "height", 0.0),
+                            is_tile: o.has_attribute("gid"),
+                        });
+                    }
+                }
+                "group" => flatten(child, raw)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    let doc = roxmltree::Document::parse(xml)?;
+    let root = doc.root_element();
+    if !root.has_tag_name("map") {
+        return Err(format_error("not a Tiled map"));
+    }
+    if attr_or(root, "infinite", 0) != 0 {
+        return Err(format_error("infinite maps are not supported"));
+    }
+
+    let mut raw = RawLevel {
+        width: attr(root, "width")?,
+        height: attr(root, "height")?,
+        tile_width: attr(root, "tilewidth")?,
+        tile_height: attr(root, "tileheight")?,
+        tile_layers: Vec::new(),
+        objects: Vec::new(),
+    };
+    flatten(root, &mut raw)?;
+
+    build_map(raw, monsters)
+}
+
+fn build_map(raw: RawLevel, monsters: &Monsters) -> Result<Map, TiledError> {
+    use EntityType::*;
+    use SpawnKind::*;
+
+    if raw.width < 3 || raw.height < 3 {
+        return Err(format_error("maps have to be at least 3x3 tiles"));
+    }
+
+    let mut map = Map::filled(raw.width, raw.height, TileType::Wall);
+
+    for (name, tiles) in raw.tile_layers {
+        let tile = match name.to_ascii_lowercase().as_str() {
+            "wall" | "walls" => TileType::Wall,
+            "floor" | "floors" => TileType::Floor,
+            "door" | "doors" => TileType::DoorClosed,
+            "opendoor" | "opendoors" => TileType::DoorOpen,
+            "water" => TileType::Water,
+            "lava" => TileType::Lava,
+            "rubble" => TileType::Rubble,
+            "crackedwall" | "crackedwalls" => TileType::CrackedWall,
+            "exit" | "exits" => TileType::Exit,
+            "stairsup" => TileType::StairsUp,
+            _ => {
+                warn!("ignoring tile layer {}", name);
+                continue;
+            }
+        };
+
+        if tiles.len() != map.cells.len() {
+            return Err(format_error(format!(
+                "layer {} has {} tiles, the map {}",
+                name,
+                tiles.len(),
+                map.cells.len()
+            )));
+        }
+
+        for (cell, gid) in map.cells.iter_mut().zip(tiles) {
+            if gid != 0 {
+                *cell = tile;
+            }
+        }
+    }
+
+    for x in 0..map.width {
+        for y in [0, map.height - 1] {
+            let idx = map.idx(x, y);
+            map.cells[idx] = TileType::Wall;
+        }
+    }
+    for y in 0..map.height {
+        for x in [0, map.width - 1] {
+            let idx = map.idx(x, y);
+            map.cells[idx] = TileType::Wall;
+        }
+    }
+
+    // nowhere to put the player, e.g. when no layer had a known name
+    if map.cells.iter().all(|t| t.blocks_movement()) {
+        return Err(format_error("the map has no open tiles"));
+    }
+
+    for o in raw.objects {
+        let top = if o.is_tile { o.y - o.height } else { o.y };
+        let pos = MapPosition {
+            x: (o.x / raw.tile_width).floor() as i32,
+            y: (top / raw.tile_height).floor() as i32,
+        };
+        if !map.in_bounds(pos.x, pos.y) {
+            warn!("ignoring {} outside the map at {:?}", o.class, pos);
+            continue;
+        }
+
+        let kind = match o.class.as_str() {
+            "Player" | "PlayerStart" => PlayerStart,
+            "Enemy" => Enemy(None),
+            "Health" => Item(Some(Health)),
+            "Ammo" => Item(Some(Ammo)),
+            "Item" => Item(None),
+            "Room" => {
+                let right = ((o.x + o.width) / raw.tile_width).ceil() as i32;
+                let bottom = ((top + o.height) / raw.tile_height).ceil() as i32;
+                map.rooms.push(Room {
+                    x: pos.x,
+                    y: pos.y,
+                    width: (right - pos.x).max(1),
+                    height: (bottom - pos.y).max(1),
+                });
+                continue;
+            }
+            class => match MonsterId::new(class).filter(|&id| monsters.get(id).is_some()) {
+                Some(id) => Enemy(Some(id)),
+                None => {
+                    warn!("ignoring object of unknown class {:?}", class);
+                    continue;
+                }
+            },
+        };
+        map.spawns.push(SpawnPoint { pos, kind });
+    }
+
+    Ok(map)
+}
+
+/// Authored levels by number. Levels without an entry are generated.
+///
+/// Loaded from a JSON list of level numbers and map files, relative to the
+/// list itself:
+///
+/// ```json
+/// [
+///     { "level": 1, "map": "intro.tmj" },
+///     { "level": 5, "map": "arena.tmx" }
+/// ]
+/// ```
+#[derive(Default)]
+pub struct LevelList {
+    levels: BTreeMap<i32, Map>,
+}
+
+impl LevelList {
+    /// Loads the list and every map on it, see `load_map`.
+    pub fn load(path: impl AsRef<Path>, monsters: &Monsters) -> Result<Self, TiledError> {
+        #[derive(Deserialize)]
+        struct Entry {
+            level: i32,
+            map: PathBuf,
+        }
+
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|e| TiledError::Io(path.to_owned(), e))?;
+        let entries: Vec<Entry> = serde_json::from_str(&json)?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut list = LevelList::default();
+        for entry in entries {
+            let map = load_map(dir.join(&entry.map), monsters).map_err(|e| {
+                format_error(format!(
+                    "level {} ({}): {}",
+                    entry.level,
+                    entry.map.display(),
+                    e
+                ))
+            })?;
+            list.insert(entry.level, map);
+        }
+
+        Ok(list)
+    }
+
+    pub fn insert(&mut self, level: i32, map: Map) {
+        self.levels.insert(level, map);
+    }
+
+    pub fn get(&self, level: i32) -> Option<&Map> {
+        self.levels.get(&level)
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+}
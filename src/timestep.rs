@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub use wasm_timer::Instant;
+
+/// Turns wall clock time into a steady stream of simulation ticks, so the game
+/// runs at the same speed however often the event loop comes around.
+///
+/// Elapsed time is added to an accumulator and every full `step` in it is one
+/// tick. Whatever is left over is how far the next tick has progressed, which
+/// rendering can use to interpolate.
+pub struct FixedTimestep {
+    step: Duration,
+    accumulator: Duration,
+    // ticks owed beyond this are dropped instead of caught up on
+    max_ticks: u32,
+    last: Option<Instant>,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: u32) -> Self {
+        FixedTimestep {
+            step: Duration::from_secs(1) / ticks_per_second,
+            accumulator: Duration::ZERO,
+            max_ticks: 8,
+            last: None,
+        }
+    }
+
+    /// Adds the time since the previous call. The first call only starts the
+    /// clock.
+    pub fn update(&mut self, now: Instant) {
+        if let Some(last) = self.last {
+            self.advance(now.duration_since(last));
+        }
+        self.last = Some(now);
+    }
+
+    /// Adds `elapsed` to the accumulator. After a long stall, e.g. a
+    /// backgrounded browser tab, at most `max_ticks` are kept so the game
+    /// doesn't fast forward to catch up.
+    pub fn advance(&mut self, elapsed: Duration) {
+        self.accumulator = (self.accumulator + elapsed).min(self.step * self.max_ticks);
+    }
+
+    /// Takes one tick's worth of time out of the accumulator, returning false
+    /// once there isn't enough left for a tick.
+    pub fn tick(&mut self) -> bool {
+        if self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// How far between the last tick and the next one we are, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
@@ -1,34 +1,37 @@
 use crate::rng::CoinFlip;
-use pixels::{Error, Pixels};
 use types::DeltaPosition;
 
 use crate::{
     components::*,
     data::*,
-    game::{play_sound, CoreGame, GameState, TheRng},
-    map::{self, Map},
-    resources::{AudioHandler, LevelStats, PlayerEntity, PlayerPosition},
+    dungeon::{self, Dungeon},
+    events::{GameEvent, GameEvents},
+    game::{GameState, TheRng},
+    input::{GameAction, TickInput},
+    map::{self, Map, SpawnKind, SpawnPoint, TileType},
+    mapgen::LevelProgression,
+    monsters::Monsters,
+    resources::{LevelStats, Objective, PlayerEntity, PlayerPosition},
     rng::Rng,
+    simulation::Simulation,
     spawn,
     systems::*,
+    tiled::LevelList,
     utils::delta_to_next_map_position,
 };
 use legion::*;
+use log::{info, warn};
 
 pub enum UpdateResult {
     None,
     Exit,
 }
 
-pub fn do_update(game: &mut CoreGame) -> Result<UpdateResult, Error> {
+pub fn do_update(game: &mut Simulation, input: &TickInput) -> UpdateResult {
     use crate::game::GameState::*;
-    use winit::event::VirtualKeyCode;
+    use GameAction::*;
 
-    // handle resize
-    if let Some(size) = game.input.window_resized() {
-        let mut pixels = game.resources.get_mut::<Pixels>().unwrap();
-        pixels.resize_surface(size.width, size.height)?;
-    }
+    game.resources.get_mut::<GameEvents>().unwrap().clear();
 
     let gamestate = *game.resources.get::<GameState>().unwrap();
 
@@ -36,11 +39,13 @@ pub fn do_update(game: &mut CoreGame) -> Result<UpdateResult, Error> {
 
     match gamestate {
         Init => {
+            // a new run starts from an empty dungeon, coming from above
+            game.resources.insert(Dungeon::default());
+            game.resources.get_mut::<LevelStats>().unwrap().level = 0;
             new_gamestate = GenerateNewMap(1);
         }
         GenerateNewMap(level) => {
-            game.resources.get_mut::<LevelStats>().unwrap().level = level;
-            let mut rng = game.resources.get_mut::<TheRng>().unwrap();
+            let from = game.resources.get::<LevelStats>().unwrap().level;
 
             game.world.push((PlayerViewportFlag,));
             game.world.push((UpdateViewshedsFlag,));
@@ -66,19 +71,19 @@ pub fn do_update(game: &mut CoreGame) -> Result<UpdateResult, Error> {
                 game.world.remove(e);
             }
 
-            let mut map = map::Map::new(10 + level, 6 + level, &mut rng);
-            map.update_blocks_visibility();
-            map.update_blocks_movement();
-
-            // restore health and ammo
-            let mut query = <(&PlayerFlag, &mut Health, &mut Ammo)>::query();
-            for (_, health, ammo) in query.iter_mut(&mut game.world) {
-                health.0 = 10;
-                ammo.0 = 10;
-            }
+            let stored = game.resources.get_mut::<Dungeon>().unwrap().take(level);
+            let (map, open_spot) = match stored {
+                Some(floor) => match dungeon::restore_floor(game, floor, level < from) {
+                    Ok(restored) => restored,
+                    Err(e) => {
+                        warn!("level {}: can't come back to it: {}", level, e);
+                        generate_floor(game, level)
+                    }
+                },
+                None => generate_floor(game, level),
+            };
 
             // put player in random spot
-            let open_spot = map.random_open_spot(&mut rng);
             let mut query = <(&PlayerFlag, &mut MapPosition)>::query();
             for (_, MapPosition { x, y }) in query.iter_mut(&mut game.world) {
                 *x = open_spot.x;
@@ -87,29 +92,12 @@ pub fn do_update(game: &mut CoreGame) -> Result<UpdateResult, Error> {
             {
                 // update playerposition resource as well
                 let mut p = game.resources.get_mut::<PlayerPosition>().unwrap();
-                p.0 = open_spot;
+                *p = PlayerPosition(open_spot, None);
             }
 
             //let viewport = map::MapViewport::new(20, 12, &map);
             let viewport = map::MapViewport::new(map.width.min(20), map.height.min(10), &map);
 
-            let player_pos = game.resources.get::<PlayerPosition>().unwrap().0;
-            for _ in 0..level * 2 {
-                spawn::spawn_enemy(&mut game.world, &map, &mut rng, &player_pos, level);
-            }
-
-            for _ in 0..level {
-                use CoinFlip::*;
-                use EntityType::*;
-                let etype = match rng.coin_flip() {
-                    Heads => Ammo,
-                    Tails => Health,
-                };
-                spawn::spawn_items(&mut game.world, &map, &mut rng, &player_pos, etype);
-            }
-
-            drop(rng);
-
             game.resources.insert(map);
             game.resources.insert(viewport);
 
@@ -122,6 +110,12 @@ pub fn do_update(game: &mut CoreGame) -> Result<UpdateResult, Error> {
         Running => {
             use types::Facing;
 
+            // before anything happens this tick, so a save made on exit
+            // doesn't miss the effects of undispatched events
+            if input.pressed(Quit) {
+                return UpdateResult::Exit;
+            }
+
             let schedule = game
                 .schedule_bag
                 .schedules
@@ -130,63 +124,67 @@ pub fn do_update(game: &mut CoreGame) -> Result<UpdateResult, Error> {
 
             schedule.execute(&mut game.world, &mut game.resources);
 
-            if game.input.key_pressed(VirtualKeyCode::Escape)
-                || game.input.close_requested()
-                || game.input.destroyed()
-            {
-                return Ok(UpdateResult::Exit);
-            }
-
-            if game.input.key_held(VirtualKeyCode::W) {
+            if input.held(MoveUp) {
                 try_move_player(game, MapPosition { x: 0, y: -1 }, Facing::Up);
             }
 
-            if game.input.key_held(VirtualKeyCode::S) {
+            if input.held(MoveDown) {
                 try_move_player(game, MapPosition { x: 0, y: 1 }, Facing::Down);
             }
 
-            if game.input.key_held(VirtualKeyCode::A) {
+            if input.held(MoveLeft) {
                 try_move_player(game, MapPosition { x: -1, y: 0 }, Facing::Left);
             }
 
-            if game.input.key_held(VirtualKeyCode::D) {
+            if input.held(MoveRight) {
                 try_move_player(game, MapPosition { x: 1, y: 0 }, Facing::Right);
             }
 
-            if game.input.key_pressed(VirtualKeyCode::Up) {
+            if input.pressed(FireUp) {
                 change_facing(game, Facing::Up);
                 fire_projectile(game);
             }
 
-            if game.input.key_pressed(VirtualKeyCode::Down) {
+            if input.pressed(FireDown) {
                 change_facing(game, Facing::Down);
                 fire_projectile(game);
             }
 
-            if game.input.key_pressed(VirtualKeyCode::Left) {
+            if input.pressed(FireLeft) {
                 change_facing(game, Facing::Left);
                 fire_projectile(game);
             }
 
-            if game.input.key_pressed(VirtualKeyCode::Right) {
+            if input.pressed(FireRight) {
                 change_facing(game, Facing::Right);
                 fire_projectile(game);
             }
 
-            if game.input.key_pressed(VirtualKeyCode::Space) {
+            if input.pressed(Fire) {
                 fire_projectile(game);
             }
 
             if dead_ememy_remover_system(game) {
                 new_gamestate = PlayerDead;
-            } else if check_map_complete_system(game) {
+            } else if let Some(next) = next_level(game) {
                 let level = game.resources.get::<LevelStats>().unwrap().level;
-                new_gamestate = GenerateNewMap(level + 1);
+                dungeon::leave_floor(game, next > level);
+                new_gamestate = GenerateNewMap(next);
             }
+
+            dispatch_events_system(game);
             update_text_info_system(game);
         }
         PlayerDead => {
-            if game.input.key_pressed(VirtualKeyCode::Space) {
+            if input.pressed(Confirm) {
+                // every run gets a seed of its own, drawn from the last one,
+                // so the seed on the death screen reproduces the run played
+                let seed = {
+                    let mut rng = game.resources.get_mut::<TheRng>().unwrap();
+                    rand::Rng::gen::<u64>(&mut *rng).to_string()
+                };
+                info!("RNG Seed: {}", seed);
+                game.restart(&seed).unwrap();
                 new_gamestate = Init;
             }
         }
@@ -194,10 +192,197 @@ pub fn do_update(game: &mut CoreGame) -> Result<UpdateResult, Error> {
 
     game.resources.insert(new_gamestate);
 
-    Ok(UpdateResult::None)
+    UpdateResult::None
 }
 
-fn try_move_player(game: &mut CoreGame, delta: MapPosition, new_facing: types::Facing) {
+// Lays out a level the player hasn't been on and fills it with monsters and
+// items. Returns its map, not inserted yet, and where the player starts.
+fn generate_floor(game: &mut Simulation, level: i32) -> (Map, MapPosition) {
+    let objective = game
+        .resources
+        .get::<LevelProgression>()
+        .unwrap()
+        .objective(level);
+    game.resources.insert(LevelStats {
+        level,
+        objective,
+        cleared: false,
+    });
+    let mut rng = game.resources.get_mut::<TheRng>().unwrap();
+
+    // authored levels only get the spawns placed in them
+    let authored = game
+        .resources
+        .get::<LevelList>()
+        .and_then(|levels| levels.get(level).cloned());
+    let mut random_spawns = authored.is_none();
+    let generate = |rng: &mut TheRng| {
+        game.resources
+            .get::<LevelProgression>()
+            .unwrap()
+            .generator(level)
+            .generate(10 + level, 6 + level, level, rng)
+    };
+    let mut map = authored.unwrap_or_else(|| generate(&mut rng));
+    // a map without a single open tile is replaced by a generated one
+    let mut open_spot = loop {
+        map.update_blocks_visibility();
+        map.update_blocks_movement();
+        map.keep_largest_region();
+
+        match spawn::player_start(&map, &mut rng) {
+            Ok(pos) => break pos,
+            Err(e) => {
+                warn!("level {}: {}, generating another map", level, e);
+                random_spawns = true;
+                map = generate(&mut rng);
+            }
+        }
+    };
+
+    // restore health and ammo
+    let mut query = <(&PlayerFlag, &mut Health, &mut Ammo)>::query();
+    for (_, health, ammo) in query.iter_mut(&mut game.world) {
+        health.0 = 10;
+        ammo.0 = 10;
+    }
+
+    // levels below the first one are entered by stairs up, next to them if
+    // there's any room
+    if level > 1 {
+        let idx = map.idx(open_spot.x, open_spot.y);
+        let tile = map.cells[idx];
+        map.set_tile(idx, TileType::StairsUp);
+        match spawn::arrival_spot(&map, &mut rng, TileType::StairsUp, &[]) {
+            Ok(pos) if pos != open_spot => open_spot = pos,
+            // standing on them would take the player straight back up
+            _ => {
+                warn!(
+                    "level {}: no room next to the stairs up, leaving them out",
+                    level
+                );
+                map.set_tile(idx, tile);
+            }
+        }
+    }
+
+    // as far from the start as it gets, unless the map has its own
+    if objective.has_exit() && !map.cells.contains(&TileType::Exit) {
+        match spawn::exit_spot(&map, open_spot) {
+            Some(pos) => {
+                let idx = map.idx(pos.x, pos.y);
+                map.set_tile(idx, TileType::Exit);
+            }
+            None => warn!("level {}: no room for an exit", level),
+        }
+    }
+
+    // prefab markers first, they count towards the level's totals
+    let monsters = game.resources.get::<Monsters>().unwrap();
+    let mut enemies = 0;
+    let mut items = 0;
+    for &SpawnPoint { pos, kind } in map.spawns.iter() {
+        match kind {
+            SpawnKind::Enemy(monster_type) => {
+                let world = &mut game.world;
+                let r = spawn::spawn_enemy_at(
+                    world,
+                    &map,
+                    &mut rng,
+                    &open_spot,
+                    level,
+                    &monsters,
+                    pos,
+                    monster_type,
+                );
+                enemies += r.is_some() as i32;
+            }
+            SpawnKind::Item(item_type) => {
+                let item_type = item_type.unwrap_or_else(|| match rng.coin_flip() {
+                    CoinFlip::Heads => EntityType::Ammo,
+                    CoinFlip::Tails => EntityType::Health,
+                });
+                let world = &mut game.world;
+                let r = spawn::spawn_item_at(world, &map, &open_spot, item_type, pos);
+                items += r.is_some() as i32;
+            }
+            SpawnKind::PlayerStart => (),
+        }
+    }
+
+    if !random_spawns {
+        (enemies, items) = (level * 2, level);
+    }
+
+    for _ in enemies..level * 2 {
+        let world = &mut game.world;
+        let r = spawn::spawn_enemy(world, &map, &mut rng, &open_spot, level, &monsters);
+        if let Err(e) = r {
+            warn!("level {}: stopped spawning enemies: {}", level, e);
+            break;
+        }
+    }
+
+    for _ in items..level {
+        use CoinFlip::*;
+        use EntityType::*;
+        let etype = match rng.coin_flip() {
+            Heads => Ammo,
+            Tails => Health,
+        };
+        let r = spawn::spawn_items(&mut game.world, &map, &mut rng, &open_spot, etype);
+        if let Err(e) = r {
+            warn!("level {}: stopped spawning items: {}", level, e);
+            break;
+        }
+    }
+
+    (map, open_spot)
+}
+
+// Emits LevelCleared and the stairs events as they happen and returns the
+// level to go to once the player is done with this one.
+fn next_level(game: &mut Simulation) -> Option<i32> {
+    let all_dead = check_map_complete_system(game);
+    let standing_on = {
+        let map = game.resources.get::<Map>().unwrap();
+        let PlayerPosition(pos, next) = *game.resources.get::<PlayerPosition>().unwrap();
+        next.map_or(Some(map.cells[map.idx(pos.x, pos.y)]), |_| None)
+    };
+
+    let mut stats = game.resources.get_mut::<LevelStats>().unwrap();
+    let mut events = game.resources.get_mut::<GameEvents>().unwrap();
+    let level = stats.level;
+
+    let newly_cleared = all_dead && !stats.cleared;
+    if newly_cleared {
+        stats.cleared = true;
+        events.emit(GameEvent::LevelCleared { level });
+    }
+
+    // on clear-all and both levels the exit only leads anywhere once cleared
+    let exit_open = match stats.objective {
+        Objective::ClearAll | Objective::Both => stats.cleared,
+        Objective::ReachExit | Objective::Either => true,
+    };
+    let on_exit = standing_on == Some(TileType::Exit) && exit_open;
+    if on_exit {
+        events.emit(GameEvent::ExitReached { level });
+    }
+
+    if standing_on == Some(TileType::StairsUp) && level > 1 {
+        events.emit(GameEvent::StairsUpReached { level });
+        return Some(level - 1);
+    }
+
+    let done = match stats.objective {
+        Objective::ClearAll | Objective::Either => newly_cleared || on_exit,
+        Objective::ReachExit | Objective::Both => on_exit,
+    };
+    done.then_some(level + 1)
+}
+
+fn try_move_player(game: &mut Simulation, delta: MapPosition, new_facing: types::Facing) {
     // check if currently moving
     {
         let player_entry = game.world.entry(game.entities.player).unwrap();
@@ -208,17 +393,30 @@ fn try_move_player(game: &mut CoreGame, delta: MapPosition, new_facing: types::F
 
     let map = game.resources.get::<Map>().unwrap();
     let mut rng = game.resources.get_mut::<TheRng>().unwrap();
-
-    // gather enemy locations
-    let mut query = <(Entity, &MapPosition, Option<&Moving>, &EnemyFlag)>::query();
+    let monsters = game.resources.get::<Monsters>().unwrap();
+
+    // gather enemy locations, and how hard they hit back
+    let mut query = <(
+        Entity,
+        &MapPosition,
+        Option<&Moving>,
+        &EnemyFlag,
+        Option<&EntityType>,
+    )>::query();
     let mut mob_positions = Vec::new();
-    for (&entity, mpos @ &MapPosition { x, y }, moving, _enemy) in query.iter_mut(&mut game.world) {
-        mob_positions.push((entity, map.idx(x, y)));
+    for (&entity, mpos @ &MapPosition { x, y }, moving, _enemy, entity_type) in
+        query.iter_mut(&mut game.world)
+    {
+        let damage = match entity_type {
+            Some(&EntityType::Monster(id)) => monsters.get(id).map_or(1, |def| def.damage),
+            _ => 1,
+        };
+        mob_positions.push((entity, map.idx(x, y), damage));
         if let Some(idx) = moving
             .map(|m| delta_to_next_map_position(mpos, &m.delta))
             .map(|p| map.idx(p.x, p.y))
         {
-            mob_positions.push((entity, idx))
+            mob_positions.push((entity, idx, damage))
         }
     }
 
@@ -231,8 +429,7 @@ fn try_move_player(game: &mut CoreGame, delta: MapPosition, new_facing: types::F
     let player_pos = entry.get_component::<MapPosition>().unwrap();
     let target_pos = (player_pos.x + delta.x, player_pos.y + delta.y);
     let target_idx = map.idx(target_pos.0, target_pos.1);
-
-    let map = game.resources.get::<Map>().unwrap();
+    let target_tile = map.cells[target_idx];
 
     // adjust facing
     if current_facing != new_facing {
@@ -240,30 +437,57 @@ fn try_move_player(game: &mut CoreGame, delta: MapPosition, new_facing: types::F
         rend.facing = new_facing;
     }
 
-    if map.blocks_movement[target_idx] {
-        play_sound("hit3", &game.resources.get::<AudioHandler>().unwrap());
-    } else if let Some((mentity, _midx)) = mob_positions.iter().find(|(_e, idx)| *idx == target_idx)
+    if let Some(opened) = target_tile.opened() {
+        drop(map);
+        game.resources
+            .get_mut::<Map>()
+            .unwrap()
+            .set_tile(target_idx, opened);
+        game.resources
+            .get_mut::<GameEvents>()
+            .unwrap()
+            .emit(GameEvent::DoorOpened {
+                pos: MapPosition {
+                    x: target_pos.0,
+                    y: target_pos.1,
+                },
+            });
+        game.world.push((UpdateViewshedsFlag,));
+        game.world.push((PlayerViewportFlag,));
+    } else if map.blocks_movement[target_idx] {
+        game.resources
+            .get_mut::<GameEvents>()
+            .unwrap()
+            .emit(GameEvent::WallBumped {
+                pos: MapPosition {
+                    x: target_pos.0,
+                    y: target_pos.1,
+                },
+            });
+    } else if let Some(&(mentity, _midx, damage)) =
+        mob_positions.iter().find(|(_e, idx, _)| *idx == target_idx)
     {
         let &Health(strength) = entry.get_component::<Health>().unwrap();
         game.world.push((DealDamage {
-            target: *mentity,
+            target: mentity,
             amount: strength,
         },));
 
         if rng.d100() <= 55 {
             game.world.push((DealDamage {
                 target: game.entities.player,
-                amount: 1,
+                amount: damage,
             },));
         }
     } else {
+        let ticks = PLAYER_MOVE_TICKS as i32 * target_tile.move_ticks_factor();
         let delta_f = DeltaPosition {
-            x: delta.x as f32 / PLAYER_MOVE_TICKS as f32,
-            y: delta.y as f32 / PLAYER_MOVE_TICKS as f32,
+            x: delta.x as f32 / ticks as f32,
+            y: delta.y as f32 / ticks as f32,
         };
         let mover = Moving {
-            ticks_left: PLAYER_MOVE_TICKS as i32,
-            total_ticks: PLAYER_MOVE_TICKS as i32,
+            ticks_left: ticks,
+            total_ticks: ticks,
             delta: delta_f,
         };
         entry.add_component(mover);
@@ -278,7 +502,7 @@ fn try_move_player(game: &mut CoreGame, delta: MapPosition, new_facing: types::F
     }
 }
 
-fn change_facing(game: &mut CoreGame, new_facing: types::Facing) {
+fn change_facing(game: &mut Simulation, new_facing: types::Facing) {
     // check if currently moving
     {
         let player_entry = game.world.entry(game.entities.player).unwrap();
@@ -293,7 +517,7 @@ fn change_facing(game: &mut CoreGame, new_facing: types::Facing) {
     rend.facing = new_facing;
 }
 
-fn fire_projectile(game: &mut CoreGame) {
+fn fire_projectile(game: &mut Simulation) {
     use types::Facing::*;
 
     let player_pos = game.resources.get::<PlayerPosition>().unwrap();
@@ -336,12 +560,16 @@ fn fire_projectile(game: &mut CoreGame) {
         delta,
     };
 
-    play_sound("fire1", &game.resources.get::<AudioHandler>().unwrap());
+    game.resources
+        .get_mut::<GameEvents>()
+        .unwrap()
+        .emit(GameEvent::ShotFired { pos, facing });
 
     let rend = RenderableSprite {
         sprite_x: 5,
         sprite_y: 1,
         facing: types::Facing::None,
+        flip: false,
     };
 
     let components = (
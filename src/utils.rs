@@ -1,4 +1,3 @@
-use num_traits::Signed;
 use types::DeltaPosition;
 
 use crate::components::*;
@@ -0,0 +1,212 @@
+use centsyl::{
+    animations::{AnimationError, Animations},
+    components::{types::*, *},
+    monsters::{MonsterId, Monsters},
+    sprites::get_renderable,
+};
+
+fn monster(name: &str) -> EntityType {
+    EntityType::Monster(MonsterId::new(name).unwrap())
+}
+
+// the cells shown over `ticks` calls, starting from a fresh state
+fn play(animations: &Animations, typ: EntityType, action: Action, ticks: usize) -> Vec<(u8, u8)> {
+    let mut anim = AnimationState::default();
+    (0..ticks)
+        .map(|_| {
+            let rend = get_renderable(
+                &typ,
+                ActionState(action),
+                &mut anim,
+                Facing::None,
+                animations,
+            );
+            (rend.sprite_x, rend.sprite_y)
+        })
+        .collect()
+}
+
+fn invalid(json: &str) -> String {
+    match Animations::from_json(json) {
+        Err(AnimationError::Invalid(msg)) => msg,
+        other => panic!("expected a validation error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn built_in_clips_cover_everything() {
+    let animations = Animations::default();
+    let mut types = vec![
+        EntityType::Player,
+        EntityType::Projectile,
+        EntityType::Explosion,
+        EntityType::Health,
+        EntityType::Ammo,
+    ];
+    types.extend(
+        Monsters::default()
+            .iter()
+            .map(|d| EntityType::Monster(d.id)),
+    );
+
+    for typ in types {
+        for action in [Action::Stationary, Action::Moving] {
+            for facing in [
+                Facing::None,
+                Facing::Right,
+                Facing::Left,
+                Facing::Up,
+                Facing::Down,
+            ] {
+                assert!(
+                    animations.clip(&typ, action, facing).is_some(),
+                    "no clip for {:?} {:?} facing {:?}",
+                    typ,
+                    action,
+                    facing
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn loop_modes() {
+    let animations = Animations::from_json(
+        r#"[
+            { "kind": "Projectile", "frames": [
+                { "x": 1, "y": 0, "ticks": 2 }, { "x": 2, "y": 0, "ticks": 1 }, { "x": 3, "y": 0, "ticks": 1 }
+            ] },
+            { "kind": "Explosion", "loop": "Once", "frames": [
+                { "x": 1, "y": 0, "ticks": 2 }, { "x": 2, "y": 0, "ticks": 1 }, { "x": 3, "y": 0, "ticks": 1 }
+            ] },
+            { "kind": "Ammo", "loop": "PingPong", "frames": [
+                { "x": 1, "y": 0, "ticks": 2 }, { "x": 2, "y": 0, "ticks": 1 }, { "x": 3, "y": 0, "ticks": 1 }
+            ] }
+        ]"#,
+    )
+    .unwrap();
+    let xs = |typ| {
+        play(&animations, typ, Action::Stationary, 9)
+            .into_iter()
+            .map(|(x, _)| x)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(xs(EntityType::Projectile), [1, 1, 2, 3, 1, 1, 2, 3, 1]);
+    assert_eq!(xs(EntityType::Explosion), [1, 1, 2, 3, 3, 3, 3, 3, 3]);
+    assert_eq!(xs(EntityType::Ammo), [1, 1, 2, 3, 2, 1, 1, 2, 3]);
+}
+
+#[test]
+fn switching_clips_starts_from_the_first_frame() {
+    let animations = Animations::default();
+    let mut anim = AnimationState::default();
+    let mut sprite = |action| {
+        let rend = get_renderable(
+            &EntityType::Player,
+            ActionState(action),
+            &mut anim,
+            Facing::Left,
+            &animations,
+        );
+        (rend.sprite_x, rend.sprite_y)
+    };
+
+    for _ in 0..100 {
+        sprite(Action::Moving);
+    }
+    assert_eq!(sprite(Action::Moving), (6, 1));
+    assert_eq!(sprite(Action::Stationary), (6, 1));
+    assert_eq!(sprite(Action::Moving), (38, 1));
+}
+
+#[test]
+fn flip_follows_the_facing() {
+    let animations = Animations::default();
+    let flip = |typ: EntityType, facing| {
+        let mut anim = AnimationState::default();
+        let stationary = ActionState(Action::Stationary);
+        get_renderable(&typ, stationary, &mut anim, facing, &animations).flip
+    };
+
+    // the built-in clips mirror everything facing right
+    assert!(flip(EntityType::Player, Facing::Right));
+    assert!(!flip(EntityType::Player, Facing::Left));
+    assert!(!flip(EntityType::Player, Facing::Up));
+    assert!(flip(EntityType::Projectile, Facing::Right));
+    assert!(flip(EntityType::Ammo, Facing::Right));
+    assert!(flip(monster("MonsterA"), Facing::Right));
+    assert!(!flip(monster("MonsterA"), Facing::None));
+
+    // clips of a list of your own only flip where they say so
+    let animations =
+        Animations::from_json(r#"[{ "kind": "Ammo", "frames": [{ "x": 1, "y": 2 }] }]"#).unwrap();
+    let mut anim = AnimationState::default();
+    let stationary = ActionState(Action::Stationary);
+    assert!(
+        !get_renderable(
+            &EntityType::Ammo,
+            stationary,
+            &mut anim,
+            Facing::Right,
+            &animations
+        )
+        .flip
+    );
+}
+
+#[test]
+fn monster_clips_replace_their_frames() {
+    let animations = Animations::from_json(
+        r#"[{ "kind": "MonsterB", "actions": ["Moving"], "frames": [{ "x": 20, "y": 4 }] }]"#,
+    )
+    .unwrap()
+    .with_monsters(&Monsters::default());
+
+    assert_eq!(
+        play(&animations, monster("MonsterB"), Action::Moving, 1),
+        [(20, 4)]
+    );
+    // the rest still come from monsters.json
+    assert_eq!(
+        play(&animations, monster("MonsterB"), Action::Stationary, 1),
+        [(41, 1)]
+    );
+    assert_eq!(
+        play(&animations, monster("MonsterA"), Action::Moving, 1),
+        [(8, 1)]
+    );
+    // kinds nobody defined show the top left cell
+    assert_eq!(
+        play(&animations, monster("Nobody"), Action::Moving, 1),
+        [(0, 0)]
+    );
+}
+
+#[test]
+fn broken_clips_are_rejected() {
+    assert!(invalid(r#"[{ "kind": "Ammo", "frames": [] }]"#).contains("no frames"));
+    assert!(
+        invalid(r#"[{ "kind": "Ammo", "frames": [{ "x": 1, "y": 40 }] }]"#)
+            .contains("off the sprite sheet")
+    );
+    assert!(
+        invalid(r#"[{ "kind": "Ammo", "frames": [{ "x": 1, "y": 2 }, { "x": 1, "y": 3 }] }]"#)
+            .contains("without ticks")
+    );
+    assert!(invalid(
+        r#"[
+            { "kind": "Player", "facings": ["Up", "Down"], "frames": [{ "x": 6, "y": 0 }] },
+            { "kind": "Player", "actions": ["Moving"], "frames": [{ "x": 6, "y": 1 }] }
+        ]"#
+    )
+    .contains("Player has two clips for Moving facing Up"));
+
+    assert!(matches!(
+        Animations::from_json(
+            r#"[{ "kind": "Ammo", "frames": [{ "x": 1, "y": 2 }], "loop": "Twice" }]"#
+        ),
+        Err(AnimationError::Json(_))
+    ));
+}
@@ -0,0 +1,311 @@
+use centsyl::{
+    animations::{AnimationError, Animations, Frame, LoopMode, Tags},
+    aseprite::{AsepriteError, AsepriteSheet},
+    components::{
+        types::{Action, Facing},
+        EntityType,
+    },
+};
+use image::{Rgba, RgbaImage};
+
+// laid out like isometric.png: three 512 by 512 frames side by side, with the
+// cell at (10, 5) red in the first, green in the second and blue in the third
+fn sheet_image() -> RgbaImage {
+    let mut image = RgbaImage::new(3 * 512, 512);
+    for (frame, color) in [[255, 0, 0], [0, 255, 0], [0, 0, 255]]
+        .into_iter()
+        .enumerate()
+    {
+        let [r, g, b] = color;
+        for y in 0..16 {
+            for x in 0..16 {
+                let col = frame as u32 * 32 + 10;
+                image.put_pixel(col * 16 + x, 5 * 16 + y, Rgba([r, g, b, 255]));
+            }
+        }
+    }
+    // transparent but not black
+    image.put_pixel(13 * 16, 5 * 16, Rgba([255, 255, 255, 0]));
+    image
+}
+
+fn frame(x: u32, y: u32, size: u32, duration: u32) -> String {
+    format!(
+        r#"{{ "frame": {{ "x": {}, "y": {}, "w": {}, "h": {} }}, "rotated": false,
+            "trimmed": false, "spriteSourceSize": {{ "x": 0, "y": 0, "w": {}, "h": {} }},
+            "sourceSize": {{ "w": {}, "h": {} }}, "duration": {} }}"#,
+        x, y, size, size, size, size, size, size, duration
+    )
+}
+
+const TAGS: &str = r##"[
+    { "name": "walk", "from": 0, "to": 2, "direction": "forward", "color": "#000000ff" },
+    { "name": "back", "from": 0, "to": 1, "direction": "reverse", "color": "#000000ff" },
+    { "name": "bounce", "from": 0, "to": 2, "direction": "pingpong", "color": "#000000ff" },
+    { "name": "burst", "from": 1, "to": 2, "direction": "forward", "repeat": "2", "color": "#000000ff" }
+]"##;
+
+// the data as exported in the hash format, the default
+fn hash_data(frames: &[String], tags: &str) -> String {
+    let frames = frames
+        .iter()
+        .enumerate()
+        .map(|(i, f)| format!(r#""isometric {}.aseprite": {}"#, i, f))
+        .collect::<Vec<_>>();
+    format!(
+        r#"{{ "frames": {{ {} }},
+            "meta": {{ "app": "https://www.aseprite.org/", "image": "sheet.png",
+                "format": "RGBA8888", "size": {{ "w": 1536, "h": 512 }}, "scale": "1",
+                "frameTags": {} }} }}"#,
+        frames.join(", "),
+        tags
+    )
+}
+
+fn frames() -> Vec<String> {
+    vec![
+        frame(0, 0, 512, 100),
+        frame(512, 0, 512, 50),
+        frame(1024, 0, 512, 200),
+    ]
+}
+
+fn cells_of(frames: &[Frame]) -> Vec<(u8, u8, i16)> {
+    frames
+        .iter()
+        .map(|&Frame { x, y, ticks }| (x, y, ticks))
+        .collect()
+}
+
+fn cells(tags: &Tags, name: &str, x: u8, y: u8) -> Vec<(u8, u8, i16)> {
+    cells_of(&tags[name].cell_frames(x, y).unwrap())
+}
+
+fn invalid(json: &str, image: RgbaImage) -> String {
+    match AsepriteSheet::from_json(json, image) {
+        Err(AsepriteError::Invalid(msg)) => msg,
+        Err(e) => panic!("expected a validation error, got {}", e),
+        Ok(_) => panic!("expected a validation error"),
+    }
+}
+
+#[test]
+fn tags_run_over_whole_frames() {
+    let sheet = AsepriteSheet::from_json(&hash_data(&frames(), TAGS), sheet_image()).unwrap();
+
+    // a frame is 32 cells across, and 100, 50 and 200 ms at 60 ticks a second
+    assert_eq!(sheet.tags["walk"].size, (32, 32));
+    assert_eq!(
+        cells(&sheet.tags, "walk", 10, 5),
+        [(10, 5, 6), (42, 5, 3), (74, 5, 12)]
+    );
+    assert_eq!(
+        cells(&sheet.tags, "walk", 0, 31),
+        [(0, 31, 6), (32, 31, 3), (64, 31, 12)]
+    );
+    assert_eq!(sheet.tags["walk"].loop_mode, LoopMode::Loop);
+    assert_eq!(cells(&sheet.tags, "back", 10, 5), [(42, 5, 3), (10, 5, 6)]);
+    assert_eq!(sheet.tags["bounce"].loop_mode, LoopMode::PingPong);
+    assert_eq!(
+        cells(&sheet.tags, "burst", 10, 5),
+        [(42, 5, 3), (74, 5, 12), (42, 5, 3), (74, 5, 12)]
+    );
+    assert_eq!(sheet.tags["burst"].loop_mode, LoopMode::Once);
+
+    // cells past the frame's edge belong to the next frame
+    assert_eq!(sheet.tags["walk"].cell_frames(32, 5), None);
+}
+
+#[test]
+fn array_data_reads_the_same() {
+    let array = format!(
+        r#"{{ "frames": [{}], "meta": {{ "image": "sheet.png", "frameTags": {} }} }}"#,
+        frames().join(", "),
+        TAGS
+    );
+    let from_array = AsepriteSheet::from_json(&array, sheet_image()).unwrap();
+    let from_hash = AsepriteSheet::from_json(&hash_data(&frames(), TAGS), sheet_image()).unwrap();
+
+    assert_eq!(from_array.tags, from_hash.tags);
+}
+
+#[test]
+fn sheet_becomes_the_sprite_grid() {
+    let sheet = AsepriteSheet::from_json(&hash_data(&frames(), "[]"), sheet_image()).unwrap();
+
+    let green = sheet
+        .grid
+        .enumerate_pixels(42, 5, false)
+        .collect::<Vec<_>>();
+    assert_eq!(green.len(), 16 * 16);
+    assert!(green.iter().all(|(_, _, p)| p.0 == [0, 255, 0]));
+
+    // transparent pixels are left out like black ones
+    assert_eq!(sheet.grid.enumerate_pixels(13, 5, false).count(), 0);
+    assert_eq!(sheet.grid.cell_size(), (16, 16));
+}
+
+#[test]
+fn clips_show_a_cell_of_a_tag() {
+    let sheet = AsepriteSheet::from_json(&hash_data(&frames(), TAGS), sheet_image()).unwrap();
+
+    let animations = Animations::from_json_with_tags(
+        r#"[
+            { "kind": "Player", "actions": ["Moving"], "tag": "walk", "cell": { "x": 10, "y": 5 },
+                "flip": ["Right"] },
+            { "kind": "Player", "actions": ["Stationary"], "tag": "bounce", "cell": { "x": 6, "y": 1 },
+                "loop": "Once" }
+        ]"#,
+        &sheet.tags,
+    )
+    .unwrap();
+
+    let walking = animations
+        .clip(&EntityType::Player, Action::Moving, Facing::Right)
+        .unwrap()
+        .1;
+    assert_eq!(
+        walking.frames,
+        sheet.tags["walk"].cell_frames(10, 5).unwrap()
+    );
+    assert_eq!(walking.flip, [Facing::Right]);
+
+    let standing = animations
+        .clip(&EntityType::Player, Action::Stationary, Facing::None)
+        .unwrap()
+        .1;
+    assert_eq!(cells_of(&standing.frames)[1], (38, 1, 3));
+    assert_eq!(standing.loop_mode, LoopMode::Once);
+
+    for (json, error) in [
+        (
+            r#"[{ "kind": "Ammo", "tag": "run", "cell": { "x": 1, "y": 2 } }]"#,
+            "Ammo uses an unknown tag run",
+        ),
+        (
+            r#"[{ "kind": "Ammo", "tag": "walk", "cell": { "x": 1, "y": 2 },
+                "frames": [{ "x": 1, "y": 2 }] }]"#,
+            "both frames and a tag",
+        ),
+        (
+            r#"[{ "kind": "Ammo", "tag": "walk" }]"#,
+            "uses tag walk without a cell",
+        ),
+        (
+            r#"[{ "kind": "Ammo", "cell": { "x": 1, "y": 2 } }]"#,
+            "has a cell but no tag",
+        ),
+        (
+            r#"[{ "kind": "Ammo", "tag": "walk", "cell": { "x": 40, "y": 2 } }]"#,
+            "Ammo cell 40, 2 is outside the frames of tag walk",
+        ),
+    ] {
+        match Animations::from_json_with_tags(json, &sheet.tags) {
+            Err(AnimationError::Invalid(msg)) => assert!(msg.contains(error), "{}", msg),
+            other => panic!("expected a validation error, got {:?}", other.map(|_| ())),
+        }
+    }
+}
+
+#[test]
+fn the_built_in_sheet_reads_as_three_frames() {
+    let image = image::open(concat!(env!("CARGO_MANIFEST_DIR"), "/isometric.png"))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(image.dimensions(), (1536, 512));
+    let tags = r#"[{ "name": "walk", "from": 0, "to": 2, "direction": "forward" }]"#;
+    let array = format!(
+        r#"{{ "frames": [{}], "meta": {{ "image": "isometric.png", "frameTags": {} }} }}"#,
+        [
+            frame(0, 0, 512, 100),
+            frame(512, 0, 512, 100),
+            frame(1024, 0, 512, 100)
+        ]
+        .join(", "),
+        tags
+    );
+    let sheet = AsepriteSheet::from_json(&array, image).unwrap();
+
+    // the player's walk, as animations.json plays it by hand
+    let animations = Animations::from_json_with_tags(
+        r#"[{ "kind": "Player", "tag": "walk", "cell": { "x": 6, "y": 1 } }]"#,
+        &sheet.tags,
+    )
+    .unwrap();
+    let walking = animations
+        .clip(&EntityType::Player, Action::Moving, Facing::None)
+        .unwrap()
+        .1;
+    assert_eq!(
+        cells_of(&walking.frames),
+        [(6, 1, 6), (38, 1, 6), (70, 1, 6)]
+    );
+    for frame in walking.frames.iter() {
+        assert!(
+            sheet
+                .grid
+                .enumerate_pixels(frame.x as u32, frame.y as u32, false)
+                .count()
+                > 0
+        );
+    }
+}
+
+#[test]
+fn broken_sheets_are_rejected() {
+    let small = RgbaImage::new(32 * 16, 32 * 16);
+    assert!(invalid(&hash_data(&frames(), "[]"), small).contains("at least 96 by 32"));
+
+    let mut odd = frames();
+    odd[1] = frame(512, 0, 500, 100);
+    assert!(invalid(&hash_data(&odd, "[]"), sheet_image()).contains("frame 1 isn't lined up"));
+
+    let mut trimmed = frames();
+    trimmed[0] = trimmed[0].replace(r#""trimmed": false"#, r#""trimmed": true"#);
+    assert!(invalid(&hash_data(&trimmed, "[]"), sheet_image())
+        .contains("frame 0 is rotated or trimmed"));
+
+    let mut off_grid = frames();
+    off_grid[2] = frame(1000, 0, 512, 100);
+    assert!(invalid(&hash_data(&off_grid, "[]"), sheet_image()).contains("frame 2 isn't lined up"));
+
+    let mut past_the_edge = frames();
+    past_the_edge[2] = frame(1280, 0, 512, 100);
+    assert!(invalid(&hash_data(&past_the_edge, "[]"), sheet_image()).contains("runs off the sheet"));
+
+    let mut mixed = frames();
+    mixed[1] = frame(512, 0, 256, 100);
+    assert!(invalid(&hash_data(&mixed, "[]"), sheet_image())
+        .contains("frame 1 is 256 by 256, frame 0 512 by 512"));
+
+    let tags = r#"[{ "name": "walk", "from": 1, "to": 3, "direction": "forward" }]"#;
+    assert!(invalid(&hash_data(&frames(), tags), sheet_image()).contains("tag walk runs"));
+
+    let tags =
+        r#"[{ "name": "walk", "from": 0, "to": 0 }, { "name": "walk", "from": 1, "to": 1 }]"#;
+    assert!(invalid(&hash_data(&frames(), tags), sheet_image()).contains("twice"));
+
+    // a bigger sheet is fine, but sprites are only drawn from the usual cells
+    let far = vec![frame(100 * 16, 0, 512, 100)];
+    let tags = r#"[{ "name": "walk", "from": 0, "to": 0 }]"#;
+    let wide = RgbaImage::new(160 * 16, 32 * 16);
+    assert!(invalid(&hash_data(&far, tags), wide).contains("outside the 96 by 32 cells"));
+}
+
+#[test]
+fn load_finds_the_image_next_to_the_data() {
+    let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("aseprite");
+    std::fs::create_dir_all(&dir).unwrap();
+    sheet_image().save(dir.join("sheet.png")).unwrap();
+    std::fs::write(dir.join("sheet.json"), hash_data(&frames(), TAGS)).unwrap();
+
+    let sheet = AsepriteSheet::load(dir.join("sheet.json")).unwrap();
+    assert_eq!(sheet.tags.len(), 4);
+    assert_eq!(sheet.grid.enumerate_pixels(74, 5, false).count(), 16 * 16);
+
+    std::fs::remove_file(dir.join("sheet.png")).unwrap();
+    assert!(matches!(
+        AsepriteSheet::load(dir.join("sheet.json")),
+        Err(AsepriteError::Image(..))
+    ));
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use centsyl::{
+    audio::RecordingAudio,
+    components::*,
+    input::{GameAction, TickInput},
+    resources::AudioHandler,
+    simulation::Simulation,
+};
+use legion::*;
+
+fn scene() -> (Simulation, Entity, RecordingAudio) {
+    let (mut sim, monster) = common::scene();
+
+    let recorder = RecordingAudio::default();
+    sim.resources.insert(AudioHandler::new(recorder.clone()));
+
+    (sim, monster, recorder)
+}
+
+#[test]
+fn deal_damage_plays_hit1() {
+    let (mut sim, monster, recorder) = scene();
+
+    sim.world.push((DealDamage {
+        target: monster,
+        amount: 1,
+    },));
+    sim.step(&TickInput::default());
+
+    let played = recorder.played();
+    assert_eq!(played.len(), 1);
+    assert_eq!(played[0].sound_id, "hit1");
+    assert_eq!(played[0].tick, 0);
+}
+
+#[test]
+fn projectile_collision_plays_hit2() {
+    let (mut sim, _monster, recorder) = scene();
+
+    let fire = TickInput::default().press(GameAction::FireRight);
+    sim.step(&fire);
+    assert_eq!(recorder.played_ids(), ["fire1"]);
+
+    for _ in 0..30 {
+        sim.step(&TickInput::default());
+    }
+
+    let ids = recorder.played_ids();
+    assert!(ids.contains(&"hit2".to_owned()), "played: {:?}", ids);
+    assert!(ids.contains(&"hit1".to_owned()), "played: {:?}", ids);
+}
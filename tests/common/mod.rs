@@ -0,0 +1,79 @@
+use centsyl::{
+    components::*,
+    events::{GameEvent, GameEvents},
+    game::{GameState, TheRng},
+    input::TickInput,
+    map::{Map, MapViewport, TileType},
+    monsters::MonsterId,
+    resources::PlayerPosition,
+    simulation::Simulation,
+};
+use legion::*;
+use rand::SeedableRng;
+
+pub fn monster_c() -> MonsterId {
+    MonsterId::new("MonsterC").unwrap()
+}
+
+// open room with the player at (2, 2) and a monster a few tiles to the right
+pub fn scene() -> (Simulation, Entity) {
+    let mut sim = Simulation::new(TheRng::seed_from_u64(0));
+
+    let mut map = Map::new(10, 5, &mut TheRng::seed_from_u64(0));
+    for x in 1..map.width - 1 {
+        for y in 1..map.height - 1 {
+            let idx = map.idx(x, y);
+            map.cells[idx] = TileType::Floor;
+        }
+    }
+    map.update_blocks_movement();
+    map.update_blocks_visibility();
+
+    let player_pos = MapPosition { x: 2, y: 2 };
+    let viewport = MapViewport::new(map.width, map.height, &map);
+    sim.resources.insert(map);
+    sim.resources.insert(viewport);
+    sim.resources.insert(GameState::Running);
+    sim.resources.insert(PlayerPosition(player_pos, None));
+    *sim.world
+        .entry(sim.entities.player)
+        .unwrap()
+        .get_component_mut::<MapPosition>()
+        .unwrap() = player_pos;
+
+    let monster = sim.world.push((
+        EnemyFlag,
+        EntityType::Monster(monster_c()),
+        ActionState(types::Action::Stationary),
+        RenderableSprite::default(),
+        AnimationState::default(),
+        MapPosition { x: 5, y: 2 },
+        Viewshed {
+            visible: Vec::new(),
+            range: 8,
+        },
+        Health(3),
+    ));
+    sim.world
+        .entry(monster)
+        .unwrap()
+        .add_component(MoveTimer { time_left: 1000 });
+
+    (sim, monster)
+}
+
+// steps the simulation, collecting every event emitted along the way
+#[allow(dead_code)]
+pub fn run(sim: &mut Simulation, inputs: impl IntoIterator<Item = TickInput>) -> Vec<GameEvent> {
+    let mut events = Vec::new();
+    for input in inputs {
+        sim.step(&input);
+        events.extend(sim.resources.get::<GameEvents>().unwrap().iter().copied());
+    }
+    events
+}
+
+#[allow(dead_code)]
+pub fn idle(ticks: usize) -> impl Iterator<Item = TickInput> {
+    std::iter::repeat_n(TickInput::default(), ticks)
+}
@@ -0,0 +1,237 @@
+use centsyl::{
+    components::*,
+    dungeon::{self, Dungeon, StoredEnemy, StoredItem},
+    game::GameState,
+    input::TickInput,
+    map::{Map, TileType},
+    mapgen::{LevelProgression, Rooms},
+    resources::{LevelStats, Objective, PlayerPosition},
+    save::{load_game, save_game},
+    simulation::Simulation,
+    tiled::LevelList,
+};
+use legion::*;
+
+// a run on level 1 of a dungeon whose levels are left by the exit
+fn start(seed: u64) -> Simulation {
+    let mut sim = Simulation::from_seed(&seed.to_string()).unwrap();
+    sim.resources.insert(
+        LevelProgression::new()
+            .then(1, Rooms::default())
+            .objective_from(1, Objective::ReachExit),
+    );
+
+    // Init, then GenerateNewMap(1)
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+    sim
+}
+
+fn level(sim: &Simulation) -> i32 {
+    sim.resources.get::<LevelStats>().unwrap().level
+}
+
+fn player_pos(sim: &Simulation) -> MapPosition {
+    *sim.world
+        .entry_ref(sim.entities.player)
+        .unwrap()
+        .get_component::<MapPosition>()
+        .unwrap()
+}
+
+fn find(sim: &Simulation, tile: TileType) -> MapPosition {
+    let map = sim.resources.get::<Map>().unwrap();
+    let idx = map.cells.iter().position(|&t| t == tile).unwrap();
+    let (x, y) = map.rev_idx(idx);
+    MapPosition { x, y }
+}
+
+// puts the player on the given stairs and lets the level change happen
+fn take_stairs(sim: &mut Simulation, stairs: TileType) {
+    let pos = find(sim, stairs);
+    *sim.world
+        .entry(sim.entities.player)
+        .unwrap()
+        .get_component_mut::<MapPosition>()
+        .unwrap() = pos;
+    sim.resources.insert(PlayerPosition(pos, None));
+
+    sim.step(&TickInput::default());
+    assert!(matches!(sim.game_state(), GameState::GenerateNewMap(_)));
+    sim.step(&TickInput::default());
+}
+
+fn enemies(sim: &Simulation) -> Vec<StoredEnemy> {
+    let mut query = <(&EntityType, &MapPosition, &Health, &EnemyFlag)>::query();
+    let mut enemies = query
+        .iter(&sim.world)
+        .filter_map(|(&typ, &pos, &Health(health), _)| match typ {
+            EntityType::Monster(monster_type) => Some(StoredEnemy {
+                monster_type,
+                pos,
+                health,
+            }),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    enemies.sort_by_key(|e| (e.pos.x, e.pos.y));
+    enemies
+}
+
+fn items(sim: &Simulation) -> Vec<StoredItem> {
+    let mut query = <(&EntityType, &MapPosition)>::query();
+    let mut items = query
+        .iter(&sim.world)
+        .filter(|&(et, _)| *et == EntityType::Ammo || *et == EntityType::Health)
+        .map(|(&item, &pos)| StoredItem { item, pos })
+        .collect::<Vec<_>>();
+    items.sort_by_key(|i| (i.pos.x, i.pos.y));
+    items
+}
+
+fn next_to(a: MapPosition, b: MapPosition) -> bool {
+    (a.x - b.x).abs() + (a.y - b.y).abs() == 1
+}
+
+#[test]
+fn levels_are_kept_when_going_down_and_back_up() {
+    for seed in 0..5 {
+        let mut sim = start(seed);
+        {
+            let mut map = sim.resources.get_mut::<Map>().unwrap();
+            map.revealed_cells[0] = true;
+        }
+        let cells = sim.resources.get::<Map>().unwrap().cells.clone();
+
+        take_stairs(&mut sim, TileType::Exit);
+        assert_eq!(level(&sim), 2, "seed {}", seed);
+        assert!(next_to(player_pos(&sim), find(&sim, TileType::StairsUp)));
+
+        let stored = sim
+            .resources
+            .get::<Dungeon>()
+            .unwrap()
+            .get(1)
+            .unwrap()
+            .clone();
+        let mut left_behind = stored.enemies.clone();
+        left_behind.sort_by_key(|e| (e.pos.x, e.pos.y));
+        assert!(!left_behind.is_empty(), "seed {}", seed);
+
+        take_stairs(&mut sim, TileType::StairsUp);
+        assert_eq!(level(&sim), 1, "seed {}", seed);
+        assert!(next_to(player_pos(&sim), find(&sim, TileType::Exit)));
+
+        let map = sim.resources.get::<Map>().unwrap();
+        assert_eq!(map.cells, cells, "seed {}", seed);
+        assert!(map.revealed_cells[0], "seed {}", seed);
+        drop(map);
+        assert_eq!(enemies(&sim), left_behind, "seed {}", seed);
+
+        let dungeon = sim.resources.get::<Dungeon>().unwrap();
+        assert!(dungeon.get(1).is_none());
+        assert!(dungeon.get(2).is_some());
+    }
+}
+
+#[test]
+fn items_left_behind_can_be_fetched_later() {
+    let mut sim = start(2);
+    let left_behind = items(&sim);
+    assert!(!left_behind.is_empty());
+
+    take_stairs(&mut sim, TileType::Exit);
+    take_stairs(&mut sim, TileType::StairsUp);
+
+    assert_eq!(items(&sim), left_behind);
+}
+
+#[test]
+fn cleared_levels_can_be_gone_back_down_from() {
+    let mut sim = Simulation::from_seed("3").unwrap();
+    sim.resources
+        .insert(LevelProgression::new().then(1, Rooms::default()));
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+
+    // clearing level 1 leads down without an exit being there
+    let mut query = <(Entity, &EnemyFlag)>::query();
+    let enemies = query.iter(&sim.world).map(|(&e, _)| e).collect::<Vec<_>>();
+    for e in enemies {
+        sim.world.remove(e);
+    }
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+    assert_eq!(level(&sim), 2);
+
+    // back up, the cleared level stays put until its new exit is taken
+    take_stairs(&mut sim, TileType::StairsUp);
+    assert_eq!(level(&sim), 1);
+    sim.step(&TickInput::default());
+    assert_eq!(sim.game_state(), GameState::Running);
+
+    take_stairs(&mut sim, TileType::Exit);
+    assert_eq!(level(&sim), 2);
+}
+
+#[test]
+fn the_dungeon_is_saved_and_starts_over_on_a_new_run() {
+    let mut sim = start(4);
+    take_stairs(&mut sim, TileType::Exit);
+
+    let mut loaded = load_game(&save_game(&sim).unwrap()).unwrap();
+    assert_eq!(loaded.resources.get::<Dungeon>().unwrap().len(), 1);
+
+    take_stairs(&mut loaded, TileType::StairsUp);
+    assert_eq!(level(&loaded), 1);
+
+    loaded.resources.insert(GameState::Init);
+    loaded.step(&TickInput::default());
+    assert!(loaded.resources.get::<Dungeon>().unwrap().is_empty());
+}
+
+#[test]
+fn leaving_mid_step_stops_the_player() {
+    let mut sim = start(1);
+    let mut player_entry = sim.world.entry(sim.entities.player).unwrap();
+    player_entry.add_component(Moving {
+        ticks_left: 5,
+        total_ticks: 10,
+        delta: types::DeltaPosition { x: 16.0, y: 0.0 },
+    });
+    player_entry.get_component_mut::<ActionState>().unwrap().0 = types::Action::Moving;
+
+    dungeon::leave_floor(&mut sim, true);
+
+    let player_entry = sim.world.entry_ref(sim.entities.player).unwrap();
+    assert!(player_entry.get_component::<Moving>().is_err());
+    assert!(player_entry.get_component::<ActionState>().unwrap().0 == types::Action::Stationary);
+}
+
+#[test]
+fn levels_with_a_single_open_tile_have_no_stairs_up() {
+    let mut sim = start(3);
+    let mut map = Map::filled(3, 3, TileType::Wall);
+    let idx = map.idx(1, 1);
+    map.set_tile(idx, TileType::Floor);
+    let mut levels = LevelList::default();
+    levels.insert(2, map);
+    sim.resources.insert(levels);
+
+    take_stairs(&mut sim, TileType::Exit);
+    assert_eq!(level(&sim), 2);
+    assert_eq!(player_pos(&sim), MapPosition { x: 1, y: 1 });
+    assert!(!sim
+        .resources
+        .get::<Map>()
+        .unwrap()
+        .cells
+        .contains(&TileType::StairsUp));
+
+    // the player stays on the level rather than going back up
+    for _ in 0..10 {
+        sim.step(&TickInput::default());
+    }
+    assert_eq!(sim.game_state(), GameState::Running);
+    assert_eq!(level(&sim), 2);
+}
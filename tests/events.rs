@@ -0,0 +1,88 @@
+mod common;
+
+use centsyl::{
+    components::*,
+    events::GameEvent,
+    input::{GameAction, TickInput},
+    simulation::Simulation,
+};
+use common::{idle, run};
+use legion::*;
+
+fn player<T: legion::storage::Component>(sim: &Simulation, f: impl Fn(&T) -> i32) -> i32 {
+    f(sim
+        .world
+        .entry_ref(sim.entities.player)
+        .unwrap()
+        .get_component::<T>()
+        .unwrap())
+}
+
+#[test]
+fn shooting_a_monster_kills_it_and_clears_the_level() {
+    let (mut sim, monster) = common::scene();
+    sim.world
+        .entry(monster)
+        .unwrap()
+        .get_component_mut::<Health>()
+        .map(|h| h.0 = 1)
+        .unwrap();
+
+    let fire = TickInput::default().press(GameAction::FireRight);
+    let events = run(&mut sim, std::iter::once(fire).chain(idle(60)));
+
+    assert!(matches!(events[0], GameEvent::ShotFired { .. }));
+    assert!(events.contains(&GameEvent::ProjectileHit {
+        target: monster,
+        pos: MapPosition { x: 5, y: 2 },
+    }));
+    assert!(events.contains(&GameEvent::EnemyDamaged {
+        entity: monster,
+        monster: common::monster_c(),
+        amount: 1,
+        health: 0,
+    }));
+    assert!(events.contains(&GameEvent::EnemyKilled {
+        entity: monster,
+        monster: common::monster_c(),
+        pos: MapPosition { x: 5, y: 2 },
+    }));
+    assert!(events.contains(&GameEvent::LevelCleared { level: 1 }));
+
+    assert_eq!(player(&sim, |s: &Score| s.0), 1);
+}
+
+#[test]
+fn walking_onto_an_item_picks_it_up() {
+    let (mut sim, _monster) = common::scene();
+    sim.world
+        .entry(sim.entities.player)
+        .unwrap()
+        .get_component_mut::<Ammo>()
+        .map(|a| a.0 = 5)
+        .unwrap();
+    sim.world
+        .push((EntityType::Ammo, MapPosition { x: 3, y: 2 }));
+
+    let right = TickInput::default().hold(GameAction::MoveRight);
+    let events = run(&mut sim, std::iter::once(right).chain(idle(10)));
+
+    assert!(events.contains(&GameEvent::ItemPickedUp {
+        item: EntityType::Ammo,
+        pos: MapPosition { x: 3, y: 2 },
+    }));
+
+    assert_eq!(player(&sim, |a: &Ammo| a.0), 6);
+}
+
+#[test]
+fn walking_into_a_wall_bumps_it() {
+    let (mut sim, _monster) = common::scene();
+
+    let up = TickInput::default().hold(GameAction::MoveUp);
+    let events = run(&mut sim, std::iter::repeat_n(up, 12));
+
+    assert!(events.contains(&GameEvent::WallBumped {
+        pos: MapPosition { x: 2, y: 0 },
+    }));
+}
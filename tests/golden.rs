@@ -0,0 +1,427 @@
+//! Golden-image regression tests for map, sprite and HUD rendering.
+//!
+//! Each test builds a fixed scene, renders it off-screen and compares the
+//! result pixel-for-pixel against `tests/golden/<name>.png`. On a mismatch the
+//! actual frame and a diff image are written next to the test binary's tmp
+//! dir. Run with `UPDATE_GOLDEN=1` to accept the new output.
+
+use std::path::PathBuf;
+
+use centsyl::{
+    animations::Animations,
+    components::*,
+    game::{GameState, TheRng},
+    map::{Map, MapViewport, Projection, TileType},
+    monsters::MonsterId,
+    render::{insert_render_resources, render_frame},
+    resources::{LevelStats, PlayerPosition},
+    simulation::Simulation,
+    sprites::get_renderable,
+    systems::update_text_info_system,
+};
+use image::{Rgba, RgbaImage};
+use rand::SeedableRng;
+
+fn monster(id: &str) -> EntityType {
+    EntityType::Monster(MonsterId::new(id).unwrap())
+}
+
+fn golden_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+fn check_golden(name: &str, actual: &RgbaImage) {
+    let path = golden_dir().join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::create_dir_all(golden_dir()).unwrap();
+        actual.save(&path).unwrap();
+        return;
+    }
+
+    let expected = image::open(&path)
+        .unwrap_or_else(|e| panic!("missing golden image {}: {}", path.display(), e))
+        .to_rgba8();
+
+    assert_eq!(
+        expected.dimensions(),
+        actual.dimensions(),
+        "{}: frame size changed",
+        name
+    );
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut mismatches = 0;
+    for (x, y, a) in actual.enumerate_pixels() {
+        let e = expected.get_pixel(x, y);
+        if a == e {
+            // dim copy of the expected frame for context
+            let Rgba([r, g, b, _]) = *e;
+            diff.put_pixel(x, y, Rgba([r / 4, g / 4, b / 4, 0xff]));
+        } else {
+            mismatches += 1;
+            diff.put_pixel(x, y, Rgba([0xff, 0, 0xff, 0xff]));
+        }
+    }
+
+    if mismatches > 0 {
+        let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let actual_path = out_dir.join(format!("{}.actual.png", name));
+        let diff_path = out_dir.join(format!("{}.diff.png", name));
+        actual.save(&actual_path).unwrap();
+        diff.save(&diff_path).unwrap();
+
+        panic!(
+            "{}: {} pixels differ from {}\n  actual: {}\n  diff:   {}",
+            name,
+            mismatches,
+            path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+// builds a fully revealed map from rows of tiles, in the prefab notation
+fn ascii_map(rows: &[&str]) -> Map {
+    let width = rows[0].len() as i32;
+    let height = rows.len() as i32;
+    let mut map = Map::new(width, height, &mut TheRng::seed_from_u64(0));
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            let idx = map.idx(x as i32, y as i32);
+            map.cells[idx] = match c {
+                '#' => TileType::Wall,
+                'D' => TileType::DoorClosed,
+                '\'' => TileType::DoorOpen,
+                '~' => TileType::Water,
+                '^' => TileType::Lava,
+                ',' => TileType::Rubble,
+                '%' => TileType::CrackedWall,
+                '>' => TileType::Exit,
+                '<' => TileType::StairsUp,
+                _ => TileType::Floor,
+            };
+        }
+    }
+
+    reveal(map)
+}
+
+fn reveal(mut map: Map) -> Map {
+    map.update_blocks_movement();
+    map.update_blocks_visibility();
+    map.visible_cells.iter_mut().for_each(|v| *v = true);
+    map.revealed_cells.iter_mut().for_each(|v| *v = true);
+    map
+}
+
+fn scene(map: Map, player_pos: MapPosition) -> Simulation {
+    let mut sim = Simulation::new(TheRng::seed_from_u64(0));
+
+    let viewport = MapViewport::new(map.width.min(20), map.height.min(10), &map);
+    sim.resources.insert(map);
+    sim.resources.insert(viewport);
+    sim.resources.insert(GameState::Running);
+    sim.resources.insert(PlayerPosition(player_pos, None));
+
+    let mut entry = sim.world.entry(sim.entities.player).unwrap();
+    *entry.get_component_mut::<MapPosition>().unwrap() = player_pos;
+    let action = *entry.get_component::<ActionState>().unwrap();
+    let mut anim = *entry.get_component::<AnimationState>().unwrap();
+    let rend = get_renderable(
+        &EntityType::Player,
+        action,
+        &mut anim,
+        types::Facing::None,
+        &Animations::default(),
+    );
+    *entry.get_component_mut::<RenderableSprite>().unwrap() = rend;
+
+    update_text_info_system(&mut sim);
+
+    sim
+}
+
+#[test]
+fn seeded_map() {
+    let mut rng = TheRng::seed_from_u64(7);
+    let map = reveal(Map::new(20, 10, &mut rng));
+    let player_pos = MapPosition { x: 10, y: 4 };
+
+    let mut sim = scene(map, player_pos);
+    check_golden("seeded_map", &render_frame(&mut sim));
+}
+
+#[test]
+fn wall_autotiles() {
+    // covers every neighbour combination handled by get_wall_tile
+    let map = ascii_map(&[
+        "####################",
+        "#..................#",
+        "#.#..##..#...#.#...#",
+        "#.....#..#..##.##..#",
+        "#.###.#..##......#.#",
+        "#.....##...###.###.#",
+        "#.##.......#.#.....#",
+        "#..#..#.##.###..#..#",
+        "#.........#........#",
+        "####################",
+    ]);
+
+    let mut sim = scene(map, MapPosition { x: 1, y: 1 });
+    check_golden("wall_autotiles", &render_frame(&mut sim));
+}
+
+#[test]
+fn tile_types() {
+    let map = ascii_map(&[
+        "####################",
+        "#<...#......~~~....#",
+        "#....D....~~~~~~...#",
+        "#....#.....~~~.....#",
+        "##'###.............#",
+        "#......^^^....,,...#",
+        "#.....^^^^^...%%%..#",
+        "#......^^^....%,%..#",
+        "#.............%%%.>#",
+        "####################",
+    ]);
+
+    let mut sim = scene(map, MapPosition { x: 2, y: 2 });
+    check_golden("tile_types", &render_frame(&mut sim));
+}
+
+#[test]
+fn remembered_tiles() {
+    use types::{Action, Facing};
+
+    let mut map = ascii_map(&[
+        "####################",
+        "#........#.........#",
+        "#..~~....#....^^...#",
+        "#..~~....D....^^...#",
+        "#........#.........#",
+        "####################",
+    ]);
+    // only the left room is in view, the right one is remembered
+    for x in 10..map.width {
+        for y in 0..map.height {
+            let idx = map.idx(x, y);
+            map.visible_cells[idx] = false;
+        }
+    }
+
+    let mut sim = scene(map, MapPosition { x: 2, y: 2 });
+
+    // the item in view shows, the remembered one doesn't
+    let animations = Animations::default();
+    for x in [5, 15] {
+        let mut anim = AnimationState::default();
+        let action = ActionState(Action::Stationary);
+        let rend = get_renderable(
+            &EntityType::Ammo,
+            action,
+            &mut anim,
+            Facing::None,
+            &animations,
+        );
+        sim.world.push((
+            EntityType::Ammo,
+            OnlyVisibleInPlayerFOV,
+            action,
+            anim,
+            rend,
+            MapPosition { x, y: 4 },
+        ));
+    }
+
+    check_golden("remembered_tiles", &render_frame(&mut sim));
+}
+
+#[test]
+fn isometric() {
+    use types::{Action, Facing};
+
+    let map = ascii_map(&[
+        "####################",
+        "#<...#......~~~....#",
+        "#....D....~~~~~~...#",
+        "#....#.....~~~.....#",
+        "##'###.............#",
+        "#......^^^....,,...#",
+        "#.....^^^^^...%%%..#",
+        "#......^^^....%,%..#",
+        "#.............%%%.>#",
+        "####################",
+    ]);
+
+    let mut sim = scene(map, MapPosition { x: 3, y: 3 });
+
+    // one in the open, one behind the wall in front of it
+    let animations = Animations::default();
+    for pos in [MapPosition { x: 8, y: 3 }, MapPosition { x: 14, y: 7 }] {
+        let mut anim = AnimationState::default();
+        let action = ActionState(Action::Stationary);
+        let rend = get_renderable(
+            &monster("MonsterA"),
+            action,
+            &mut anim,
+            Facing::None,
+            &animations,
+        );
+        sim.world
+            .push((monster("MonsterA"), action, anim, rend, pos));
+    }
+
+    insert_render_resources(&mut sim.resources);
+    sim.resources.insert(Projection::Isometric);
+    check_golden("isometric", &render_frame(&mut sim));
+}
+
+// an item under an explosion and a monster stepping down towards the player,
+// with the entities spawned in either order
+fn overlapping_sprites(reversed: bool) -> Simulation {
+    use types::{Action, DeltaPosition, Facing};
+
+    let map = ascii_map(&[
+        "##########",
+        "#........#",
+        "#........#",
+        "#........#",
+        "##########",
+    ]);
+    let mut sim = scene(map, MapPosition { x: 6, y: 3 });
+
+    let mut spawns = vec![
+        (EntityType::Ammo, MapPosition { x: 2, y: 2 }, None),
+        (EntityType::Explosion, MapPosition { x: 2, y: 2 }, None),
+        (
+            monster("MonsterA"),
+            MapPosition { x: 6, y: 2 },
+            Some(Moving {
+                ticks_left: 6,
+                total_ticks: 16,
+                delta: DeltaPosition { x: 0.0, y: 1.0 },
+            }),
+        ),
+    ];
+    if reversed {
+        spawns.reverse();
+    }
+
+    let animations = Animations::default();
+    for (etype, pos, moving) in spawns {
+        let mut anim = AnimationState::default();
+        let action = ActionState(Action::Stationary);
+        let rend = get_renderable(&etype, action, &mut anim, Facing::None, &animations);
+        let entity = sim.world.push((etype, action, anim, rend, pos));
+        if let Some(moving) = moving {
+            sim.world.entry(entity).unwrap().add_component(moving);
+        }
+    }
+
+    sim
+}
+
+#[test]
+fn draw_order() {
+    let frame = render_frame(&mut overlapping_sprites(false));
+    check_golden("draw_order", &frame);
+
+    assert!(
+        frame == render_frame(&mut overlapping_sprites(true)),
+        "draw order depends on spawn order"
+    );
+}
+
+#[test]
+fn sprites() {
+    use types::{Action, Facing};
+
+    let rows: Vec<String> = (0..10)
+        .map(|y| {
+            if y == 0 || y == 9 {
+                "#".repeat(20)
+            } else {
+                format!("#{}#", ".".repeat(18))
+            }
+        })
+        .collect();
+    let rows: Vec<&str> = rows.iter().map(|r| r.as_str()).collect();
+
+    let mut sim = scene(ascii_map(&rows), MapPosition { x: 1, y: 1 });
+
+    // hide the player, it gets its own row below
+    sim.world.remove(sim.entities.player);
+
+    let entity_types = [
+        EntityType::Player,
+        monster("MonsterA"),
+        monster("MonsterB"),
+        monster("MonsterC"),
+        EntityType::Projectile,
+        EntityType::Explosion,
+        EntityType::Health,
+        EntityType::Ammo,
+    ];
+    let actions = [Action::Stationary, Action::Moving];
+    let facings = [
+        Facing::None,
+        Facing::Right,
+        Facing::Left,
+        Facing::Up,
+        Facing::Down,
+    ];
+
+    let animations = Animations::default();
+    for (row, etype) in entity_types.iter().enumerate() {
+        let mut col = 0;
+        for &action in actions.iter() {
+            for &facing in facings.iter() {
+                let mut anim = AnimationState::default();
+                let mut rend =
+                    get_renderable(etype, ActionState(action), &mut anim, facing, &animations);
+                rend.facing = facing;
+
+                sim.world.push((
+                    *etype,
+                    ActionState(action),
+                    anim,
+                    rend,
+                    MapPosition {
+                        x: 1 + col,
+                        y: 1 + row as i32,
+                    },
+                ));
+
+                col += 1;
+            }
+        }
+    }
+
+    check_golden("sprites", &render_frame(&mut sim));
+}
+
+#[test]
+fn hud() {
+    let map = ascii_map(&["#######", "#.....#", "#.....#", "#######"]);
+    let mut sim = scene(map, MapPosition { x: 2, y: 1 });
+
+    {
+        let mut entry = sim.world.entry(sim.entities.player).unwrap();
+        entry.get_component_mut::<Score>().unwrap().0 = 42;
+        entry.get_component_mut::<Health>().unwrap().0 = 7;
+        entry.get_component_mut::<Ammo>().unwrap().0 = 3;
+    }
+    sim.resources.get_mut::<LevelStats>().unwrap().level = 3;
+    sim.world.push((EnemyFlag, MapPosition { x: 4, y: 2 }));
+
+    update_text_info_system(&mut sim);
+    check_golden("hud", &render_frame(&mut sim));
+
+    sim.resources.insert(GameState::PlayerDead);
+    check_golden("player_dead", &render_frame(&mut sim));
+}
@@ -0,0 +1,74 @@
+use centsyl::input::{GameAction, InputMap};
+use winit::event::{DeviceId, ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
+use winit_input_helper::WinitInputHelper;
+
+#[allow(deprecated)]
+fn key(keycode: VirtualKeyCode, state: ElementState) -> WindowEvent<'static> {
+    WindowEvent::KeyboardInput {
+        // only ever compared, never handed to the platform
+        device_id: unsafe { DeviceId::dummy() },
+        input: KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(keycode),
+            modifiers: Default::default(),
+        },
+        is_synthetic: false,
+    }
+}
+
+#[test]
+fn partial_bindings_keep_the_other_defaults() {
+    let input_map = InputMap::from_json(r#"{ "MoveUp": ["Up", "K"], "Fire": [] }"#).unwrap();
+    let defaults = InputMap::default();
+
+    assert_eq!(
+        input_map.bindings[&GameAction::MoveUp],
+        [VirtualKeyCode::Up, VirtualKeyCode::K]
+    );
+    assert!(input_map.bindings[&GameAction::Fire].is_empty());
+    for action in GameAction::ALL {
+        if action != GameAction::MoveUp && action != GameAction::Fire {
+            assert_eq!(
+                input_map.bindings[&action], defaults.bindings[&action],
+                "{:?}",
+                action
+            );
+        }
+    }
+}
+
+#[test]
+fn broken_bindings_are_rejected() {
+    assert!(InputMap::from_json(r#"{ "MoveUp": ["W"]"#).is_err());
+    assert!(InputMap::from_json(r#"{ "Jump": ["Space"] }"#).is_err());
+    assert!(InputMap::from_json(r#"{ "MoveUp": ["NoSuchKey"] }"#).is_err());
+    assert!(InputMap::from_json(r#"["W"]"#).is_err());
+}
+
+#[test]
+fn keys_become_actions() {
+    let input_map = InputMap::from_json(r#"{ "MoveUp": ["Up", "K"] }"#).unwrap();
+    let mut input = WinitInputHelper::new();
+
+    input.step_with_window_events(&[
+        key(VirtualKeyCode::K, ElementState::Pressed),
+        key(VirtualKeyCode::Space, ElementState::Pressed),
+    ]);
+    let tick_input = input_map.tick_input(&input);
+    for action in [GameAction::MoveUp, GameAction::Fire, GameAction::Confirm] {
+        assert!(tick_input.pressed(action), "{:?}", action);
+    }
+    assert!(!tick_input.held(GameAction::MoveDown));
+
+    // W moved to K, so it does nothing any more
+    input.step_with_window_events(&[key(VirtualKeyCode::W, ElementState::Pressed)]);
+    let tick_input = input_map.tick_input(&input);
+    assert!(tick_input.held(GameAction::MoveUp));
+    assert!(!tick_input.pressed(GameAction::MoveUp));
+
+    input.step_with_window_events(&[key(VirtualKeyCode::K, ElementState::Released)]);
+    let tick_input = input_map.tick_input(&input);
+    assert!(!tick_input.held(GameAction::MoveUp));
+    assert!(tick_input.held(GameAction::Fire));
+}
@@ -0,0 +1,141 @@
+use centsyl::{
+    components::*,
+    game::{GameState, TheRng},
+    input::TickInput,
+    map::{Map, TileType},
+    mapgen::{Caves, LevelProgression, MapGenerator, Rooms, ScatteredPillars},
+    simulation::Simulation,
+};
+use legion::*;
+use rand::SeedableRng;
+
+// no walls but the border
+struct OpenRoom;
+
+impl MapGenerator for OpenRoom {
+    fn generate(&self, width: i32, height: i32, _level: i32, rng: &mut TheRng) -> Map {
+        let mut map = Map::new(width, height, rng);
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                let idx = map.idx(x, y);
+                map.cells[idx] = TileType::Floor;
+            }
+        }
+        map
+    }
+}
+
+fn interior_walls(map: &Map) -> usize {
+    (1..map.width - 1)
+        .flat_map(|x| (1..map.height - 1).map(move |y| (x, y)))
+        .filter(|&(x, y)| map.cells[map.idx(x, y)] == TileType::Wall)
+        .count()
+}
+
+#[test]
+fn progression_picks_the_latest_entry_for_a_level() {
+    let progression = LevelProgression::new()
+        .then(1, ScatteredPillars)
+        .then(3, OpenRoom);
+
+    let mut rng = TheRng::seed_from_u64(1);
+    for level in 1..=6 {
+        let map = progression
+            .generator(level)
+            .generate(30, 20, level, &mut rng);
+        assert_eq!(interior_walls(&map) == 0, level >= 3, "level {}", level);
+    }
+}
+
+#[test]
+fn new_levels_come_from_the_progression() {
+    let mut sim = Simulation::from_seed("5").unwrap();
+    sim.resources
+        .insert(LevelProgression::new().then(1, OpenRoom));
+
+    // Init, then GenerateNewMap(1)
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+
+    let map = sim.resources.get::<Map>().unwrap();
+    assert_eq!(interior_walls(&map), 0);
+}
+
+#[test]
+fn caves_are_one_connected_area() {
+    let mut rng = TheRng::seed_from_u64(3);
+    for level in 3..10 {
+        let map = Caves::default().generate(10 + level, 6 + level, level, &mut rng);
+
+        let regions = map.regions();
+        assert_eq!(regions.len(), 1, "level {}", level);
+        assert!(
+            regions[0].len() >= 8,
+            "level {}: only {} open tiles",
+            level,
+            regions[0].len()
+        );
+
+        // the border stays solid
+        for x in 0..map.width {
+            assert!(map.blocks_movement[map.idx(x, 0)]);
+            assert!(map.blocks_movement[map.idx(x, map.height - 1)]);
+        }
+        for y in 0..map.height {
+            assert!(map.blocks_movement[map.idx(0, y)]);
+            assert!(map.blocks_movement[map.idx(map.width - 1, y)]);
+        }
+    }
+}
+
+#[test]
+fn rooms_are_open_and_connected() {
+    let mut rng = TheRng::seed_from_u64(4);
+    for level in 5..12 {
+        let map = Rooms::default().generate(10 + level, 6 + level, level, &mut rng);
+
+        assert!(map.rooms.len() >= 2, "level {}", level);
+        for room in map.rooms.iter() {
+            for x in room.x..room.x + room.width {
+                for y in room.y..room.y + room.height {
+                    assert_eq!(map.cells[map.idx(x, y)], TileType::Floor);
+                }
+            }
+        }
+
+        let mut map = map;
+        map.update_blocks_movement();
+        assert_eq!(map.regions().len(), 1, "level {}", level);
+    }
+}
+
+#[test]
+fn enemies_stay_out_of_the_start_room() {
+    for seed in 0..5 {
+        let mut sim = Simulation::from_seed(&seed.to_string()).unwrap();
+        sim.resources
+            .insert(LevelProgression::new().then(1, Rooms::default()));
+        sim.step(&TickInput::default());
+        sim.resources.insert(GameState::GenerateNewMap(6));
+        sim.step(&TickInput::default());
+
+        let map = sim.resources.get::<Map>().unwrap();
+        let player = *sim
+            .world
+            .entry_ref(sim.entities.player)
+            .unwrap()
+            .get_component::<MapPosition>()
+            .unwrap();
+        let start_room = map.room_at(player).expect("player starts in a room");
+
+        let mut query = <&MapPosition>::query().filter(component::<EnemyFlag>());
+        for &pos in query.iter(&sim.world) {
+            assert!(
+                !start_room.contains(pos),
+                "seed {}: enemy at {:?}",
+                seed,
+                pos
+            );
+        }
+    }
+}
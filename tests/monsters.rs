@@ -0,0 +1,201 @@
+use centsyl::{
+    animations::Animations,
+    components::*,
+    game::TheRng,
+    map::{Map, TileType},
+    monsters::{MonsterError, MonsterId, Monsters},
+    spawn::spawn_enemy,
+    sprites::get_renderable,
+};
+use legion::*;
+use rand::SeedableRng;
+use serde_json::{json, Value};
+
+// a slow brute that only shows up from level 3 on, next to a plain one
+const CUSTOM: &str = r#"[
+    {
+        "id": "Grunt",
+        "health": 1,
+        "speed": { "sides": 4 },
+        "sight": 5,
+        "spawn": [{ "from_level": 1, "weight": 1 }, { "from_level": 3, "weight": 0 }],
+        "frames": { "stationary": [{ "x": 8, "y": 1 }], "moving": [{ "x": 8, "y": 1 }] }
+    },
+    {
+        "id": "Brute",
+        "health": 7,
+        "speed": { "count": 2, "sides": 6, "times": 50 },
+        "sight": 3,
+        "damage": 4,
+        "spawn": [{ "from_level": 3, "weight": 1 }],
+        "frames": {
+            "stationary": [{ "x": 12, "y": 3, "ticks": 5 }, { "x": 13, "y": 3, "ticks": 5 }],
+            "moving": [{ "x": 14, "y": 3 }]
+        },
+        "sounds": { "death": "hit1" }
+    }
+]"#;
+
+fn id(name: &str) -> MonsterId {
+    MonsterId::new(name).unwrap()
+}
+
+fn invalid(json: &str) -> String {
+    match Monsters::from_json(json) {
+        Err(MonsterError::Invalid(msg)) => msg,
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+}
+
+#[test]
+fn built_in_monsters_load() {
+    let monsters = Monsters::default();
+    let ids = monsters.iter().map(|d| d.id.as_str()).collect::<Vec<_>>();
+    assert_eq!(ids, ["MonsterA", "MonsterB", "MonsterC"]);
+
+    let c = monsters.get(id("MonsterC")).unwrap();
+    assert_eq!(c.health, 3);
+    assert_eq!(c.spawn_weight(1), 20);
+    assert_eq!(c.sight_range(1), 8);
+    assert_eq!(c.sight_range(12), 12);
+}
+
+#[test]
+fn spawn_weights_follow_the_level() {
+    let monsters = Monsters::from_json(CUSTOM).unwrap();
+    let mut rng = TheRng::seed_from_u64(0);
+
+    for (level, expected) in [(1, "Grunt"), (2, "Grunt"), (3, "Brute"), (9, "Brute")] {
+        for _ in 0..20 {
+            assert_eq!(monsters.roll(level, &mut rng).id, id(expected));
+        }
+    }
+}
+
+#[test]
+fn spawned_monsters_use_their_definition() {
+    let monsters = Monsters::from_json(CUSTOM).unwrap();
+    let mut rng = TheRng::seed_from_u64(0);
+
+    let mut map = Map::filled(5, 3, TileType::Wall);
+    for x in 1..4 {
+        let idx = map.idx(x, 1);
+        map.cells[idx] = TileType::Floor;
+    }
+    map.update_blocks_movement();
+    map.keep_largest_region();
+
+    let mut world = World::default();
+    let player_pos = MapPosition { x: 1, y: 1 };
+    let enemy = spawn_enemy(&mut world, &map, &mut rng, &player_pos, 3, &monsters).unwrap();
+
+    let entry = world.entry_ref(enemy).unwrap();
+    assert_eq!(
+        *entry.get_component::<EntityType>().unwrap(),
+        EntityType::Monster(id("Brute"))
+    );
+    assert_eq!(entry.get_component::<Health>().unwrap().0, 7);
+    assert_eq!(entry.get_component::<Viewshed>().unwrap().range, 3);
+    let time_left = entry.get_component::<MoveTimer>().unwrap().time_left;
+    assert!((100..=600).contains(&time_left) && time_left % 50 == 0);
+
+    let animations = Animations::default().with_monsters(&monsters);
+    let brute = EntityType::Monster(id("Brute"));
+    let mut anim = AnimationState::default();
+    let stationary = ActionState(types::Action::Stationary);
+    let frames = (0..12)
+        .map(|_| {
+            get_renderable(
+                &brute,
+                stationary,
+                &mut anim,
+                types::Facing::None,
+                &animations,
+            )
+        })
+        .map(|r| (r.sprite_x, r.sprite_y))
+        .collect::<Vec<_>>();
+    assert!(frames.contains(&(12, 3)) && frames.contains(&(13, 3)));
+
+    let moving = ActionState(types::Action::Moving);
+    let rend = get_renderable(&brute, moving, &mut anim, types::Facing::None, &animations);
+    assert_eq!((rend.sprite_x, rend.sprite_y), (14, 3));
+
+    let brute = monsters.get(id("Brute")).unwrap();
+    assert_eq!(brute.damage, 4);
+    assert_eq!(brute.sounds.hurt, "hit1");
+}
+
+#[test]
+fn broken_definitions_are_rejected() {
+    // the grunt alone, spawning everywhere, with one field replaced
+    let grunt = |field: &str, value: &str| {
+        let mut defs: Value = serde_json::from_str(CUSTOM).unwrap();
+        let mut grunt = defs[0].take();
+        grunt["spawn"] = json!([{ "from_level": 1, "weight": 1 }]);
+        if !field.is_empty() {
+            grunt[field] = serde_json::from_str(value).unwrap();
+        }
+        json!([grunt]).to_string()
+    };
+    assert!(Monsters::from_json(&grunt("", "")).is_ok());
+
+    let frames = |stationary: &str| {
+        format!(
+            r#"{{ "stationary": {}, "moving": [{{ "x": 8, "y": 1 }}] }}"#,
+            stationary
+        )
+    };
+    for (field, value, error) in [
+        ("health", "0".to_owned(), "needs some health"),
+        ("speed", r#"{ "sides": 0 }"#.to_owned(), "unrollable speed"),
+        (
+            "sounds",
+            r#"{ "hurt": "meow" }"#.to_owned(),
+            "unknown sound meow",
+        ),
+        ("frames", frames("[]"), "no stationary frames"),
+        (
+            "frames",
+            frames(r#"[{ "x": 99, "y": 1 }]"#),
+            "off the sprite sheet",
+        ),
+        (
+            "frames",
+            frames(r#"[{ "x": 8, "y": 1 }, { "x": 9, "y": 1 }]"#),
+            "stationary frames without ticks",
+        ),
+        (
+            "spawn",
+            r#"[{ "from_level": 3, "weight": 1 }, { "from_level": 2, "weight": 1 }]"#.to_owned(),
+            "out of order",
+        ),
+        (
+            "spawn",
+            r#"[{ "from_level": 1, "weight": -1 }]"#.to_owned(),
+            "negative weight",
+        ),
+        (
+            "spawn",
+            r#"[{ "from_level": 2, "weight": 1 }]"#.to_owned(),
+            "on level 1",
+        ),
+        (
+            "spawn",
+            r#"[{ "from_level": 1, "weight": 1 }, { "from_level": 4, "weight": 0 }]"#.to_owned(),
+            "on level 4",
+        ),
+    ] {
+        let msg = invalid(&grunt(field, &value));
+        assert!(msg.contains(error), "{:?} for {} {}", msg, field, value);
+    }
+
+    let grunt_def: Value = serde_json::from_str::<Value>(&grunt("", "")).unwrap()[0].take();
+    let twice = json!([grunt_def, grunt_def]).to_string();
+    assert!(invalid(&twice).contains("defined twice"));
+
+    assert!(matches!(
+        Monsters::from_json(&grunt("id", r#""""#)),
+        Err(MonsterError::Json(_))
+    ));
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use centsyl::{
+    components::*,
+    events::GameEvent,
+    input::{GameAction, TickInput},
+    map::{Map, TileType},
+    mapgen::{LevelProgression, Rooms},
+    resources::{LevelStats, Objective},
+    simulation::Simulation,
+};
+use common::{idle, run};
+use legion::*;
+
+// the common scene with an exit right next to the player
+fn scene(objective: Objective) -> (Simulation, Entity) {
+    let (sim, monster) = common::scene();
+    sim.resources.get_mut::<LevelStats>().unwrap().objective = objective;
+    {
+        let mut map = sim.resources.get_mut::<Map>().unwrap();
+        let idx = map.idx(2, 3);
+        map.set_tile(idx, TileType::Exit);
+    }
+    (sim, monster)
+}
+
+fn level(sim: &Simulation) -> i32 {
+    sim.resources.get::<LevelStats>().unwrap().level
+}
+
+fn walk_onto_exit(sim: &mut Simulation) -> Vec<GameEvent> {
+    let down = TickInput::default().hold(GameAction::MoveDown);
+    run(sim, std::iter::once(down).chain(idle(10)))
+}
+
+#[test]
+fn reaching_the_exit_ends_the_level() {
+    for objective in [Objective::ReachExit, Objective::Either] {
+        let (mut sim, _monster) = scene(objective);
+
+        let events = walk_onto_exit(&mut sim);
+
+        assert!(events.contains(&GameEvent::ExitReached { level: 1 }));
+        assert!(!events.contains(&GameEvent::LevelCleared { level: 1 }));
+        assert_eq!(level(&sim), 2);
+    }
+}
+
+#[test]
+fn the_exit_does_nothing_on_clear_all_levels() {
+    let (mut sim, _monster) = scene(Objective::ClearAll);
+
+    let events = walk_onto_exit(&mut sim);
+
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, GameEvent::ExitReached { .. })));
+    assert_eq!(level(&sim), 1);
+}
+
+#[test]
+fn both_levels_need_a_clear_before_the_exit() {
+    let (mut sim, monster) = scene(Objective::Both);
+
+    let events = walk_onto_exit(&mut sim);
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, GameEvent::ExitReached { .. })));
+    assert_eq!(level(&sim), 1);
+
+    // back off the exit, clear the level, which doesn't end it yet
+    let up = TickInput::default().hold(GameAction::MoveUp);
+    run(&mut sim, std::iter::once(up).chain(idle(10)));
+    sim.world
+        .entry(monster)
+        .unwrap()
+        .get_component_mut::<Health>()
+        .map(|h| h.0 = 1)
+        .unwrap();
+    let fire = TickInput::default().press(GameAction::FireRight);
+    let events = run(&mut sim, std::iter::once(fire).chain(idle(60)));
+    assert!(events.contains(&GameEvent::LevelCleared { level: 1 }));
+    assert_eq!(level(&sim), 1);
+
+    let events = walk_onto_exit(&mut sim);
+    assert!(events.contains(&GameEvent::ExitReached { level: 1 }));
+    assert_eq!(level(&sim), 2);
+}
+
+#[test]
+fn clearing_an_exit_level_scores_once_and_carries_on() {
+    let (mut sim, monster) = scene(Objective::ReachExit);
+    sim.world
+        .entry(monster)
+        .unwrap()
+        .get_component_mut::<Health>()
+        .map(|h| h.0 = 1)
+        .unwrap();
+
+    let fire = TickInput::default().press(GameAction::FireRight);
+    let events = run(&mut sim, std::iter::once(fire).chain(idle(60)));
+
+    let cleared = events
+        .iter()
+        .filter(|e| matches!(e, GameEvent::LevelCleared { .. }))
+        .count();
+    assert_eq!(cleared, 1);
+    assert_eq!(level(&sim), 1);
+
+    walk_onto_exit(&mut sim);
+    assert_eq!(level(&sim), 2);
+
+    let score = sim
+        .world
+        .entry_ref(sim.entities.player)
+        .unwrap()
+        .get_component::<Score>()
+        .unwrap()
+        .0;
+    assert_eq!(score, 1);
+}
+
+#[test]
+fn generated_exit_levels_get_one_reachable_exit() {
+    for seed in 0..5 {
+        let mut sim = Simulation::from_seed(&seed.to_string()).unwrap();
+        sim.resources.insert(
+            LevelProgression::new()
+                .then(1, Rooms::default())
+                .objective_from(1, Objective::ReachExit),
+        );
+
+        // Init, then GenerateNewMap(1)
+        sim.step(&TickInput::default());
+        sim.step(&TickInput::default());
+
+        let map = sim.resources.get::<Map>().unwrap();
+        let exits = (0..map.cells.len())
+            .filter(|&idx| map.cells[idx] == TileType::Exit)
+            .collect::<Vec<_>>();
+        assert_eq!(exits.len(), 1, "seed {}", seed);
+
+        let (x, y) = map.rev_idx(exits[0]);
+        let player = *sim
+            .world
+            .entry_ref(sim.entities.player)
+            .unwrap()
+            .get_component::<MapPosition>()
+            .unwrap();
+        assert!(map.is_reachable(MapPosition { x, y }), "seed {}", seed);
+        assert_ne!(player, MapPosition { x, y }, "seed {}", seed);
+
+        let stats = sim.resources.get::<LevelStats>().unwrap();
+        assert_eq!(stats.objective, Objective::ReachExit);
+    }
+}
+
+#[test]
+fn progression_objectives_apply_from_their_level_on() {
+    let progression = LevelProgression::new()
+        .objective_from(3, Objective::Either)
+        .objective_from(6, Objective::ReachExit);
+
+    assert_eq!(progression.objective(1), Objective::ClearAll);
+    assert_eq!(progression.objective(3), Objective::Either);
+    assert_eq!(progression.objective(5), Objective::Either);
+    assert_eq!(progression.objective(9), Objective::ReachExit);
+}
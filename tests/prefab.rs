@@ -0,0 +1,200 @@
+use centsyl::{
+    components::*,
+    game::{GameState, TheRng},
+    input::TickInput,
+    map::{Map, SpawnKind, SpawnPoint, TileType},
+    mapgen::{Caves, LevelProgression, MapGenerator, WithPrefabs},
+    monsters::MonsterId,
+    prefab::{Prefab, PrefabError, PrefabTile},
+    resources::PlayerPosition,
+    simulation::Simulation,
+};
+use legion::*;
+use rand::SeedableRng;
+
+// no walls but the border
+struct OpenRoom;
+
+impl MapGenerator for OpenRoom {
+    fn generate(&self, width: i32, height: i32, _level: i32, _rng: &mut TheRng) -> Map {
+        let mut map = Map::filled(width, height, TileType::Wall);
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                let idx = map.idx(x, y);
+                map.cells[idx] = TileType::Floor;
+            }
+        }
+        map
+    }
+}
+
+// runs the first level of a map laid out by `generator`
+fn first_level(seed: &str, generator: impl MapGenerator + 'static) -> Simulation {
+    let mut sim = Simulation::from_seed(seed).unwrap();
+    sim.resources
+        .insert(LevelProgression::new().then(1, generator));
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+    assert!(sim.game_state() == GameState::Running);
+    sim
+}
+
+fn enemies(sim: &Simulation) -> Vec<(MapPosition, EntityType)> {
+    let mut query = <(&MapPosition, &EntityType)>::query().filter(component::<EnemyFlag>());
+    query.iter(&sim.world).map(|(&p, &t)| (p, t)).collect()
+}
+
+#[test]
+fn builtin_prefabs_parse() {
+    let prefabs = Prefab::builtin();
+    assert!(!prefabs.is_empty());
+
+    let shrine = prefabs.iter().find(|p| p.name == "shrine").unwrap();
+    assert_eq!((shrine.width, shrine.height), (7, 5));
+    assert_eq!(shrine.tile(0, 0), PrefabTile::Keep);
+    assert_eq!(shrine.tile(1, 0), PrefabTile::Tile(TileType::Wall));
+    assert_eq!(
+        shrine.tile(2, 2),
+        PrefabTile::Spawn(SpawnKind::Item(Some(EntityType::Health)))
+    );
+}
+
+#[test]
+fn unknown_tiles_are_an_error() {
+    let err = Prefab::parse("bad", "; comment\n###\n#x#\n###\n").unwrap_err();
+    assert_eq!(
+        err,
+        PrefabError::UnknownTile {
+            name: "bad".to_owned(),
+            line: 3,
+            ch: 'x',
+        }
+    );
+}
+
+#[test]
+fn stamping_copies_tiles_and_markers() {
+    let prefab = Prefab::parse("cache", "###\n#$#\n#C#\n").unwrap();
+    let mut map = Map::filled(6, 6, TileType::Floor);
+
+    let room = prefab.stamp(&mut map, 2, 1);
+
+    assert_eq!((room.x, room.y, room.width, room.height), (2, 1, 3, 3));
+    assert_eq!(map.cells[map.idx(2, 1)], TileType::Wall);
+    assert_eq!(map.cells[map.idx(3, 2)], TileType::Floor);
+    assert_eq!(
+        map.spawns,
+        [
+            SpawnPoint {
+                pos: MapPosition { x: 3, y: 2 },
+                kind: SpawnKind::Item(Some(EntityType::Ammo)),
+            },
+            SpawnPoint {
+                pos: MapPosition { x: 3, y: 3 },
+                kind: SpawnKind::Enemy(MonsterId::new("MonsterC")),
+            },
+        ]
+    );
+}
+
+#[test]
+fn markers_spawn_what_they_ask_for() {
+    let prefab = Prefab::parse("guarded", "#####\n#$C.#\n##.##\n").unwrap();
+    let monster_c = EntityType::Monster(MonsterId::new("MonsterC").unwrap());
+
+    for seed in 0..5 {
+        let sim = first_level(
+            &seed.to_string(),
+            WithPrefabs::new(OpenRoom, vec![prefab.clone()], 1),
+        );
+        let map = sim.resources.get::<Map>().unwrap();
+
+        let guard = map.spawns[1].pos;
+        let player = sim.resources.get::<PlayerPosition>().unwrap().0;
+        if map.area_at(player).is_some_and(|r| r.contains(guard)) {
+            continue;
+        }
+        assert!(enemies(&sim).contains(&(guard, monster_c)), "seed {}", seed);
+
+        let mut query = <(&MapPosition, &EntityType)>::query();
+        assert!(query
+            .iter(&sim.world)
+            .any(|(&p, &t)| p == map.spawns[0].pos && t == EntityType::Ammo));
+    }
+}
+
+#[test]
+fn enemies_are_kept_out_of_the_start_room() {
+    let prefab = Prefab::parse("ambush", "#####\n#@.C#\n##.##\n").unwrap();
+
+    let sim = first_level("1", WithPrefabs::new(OpenRoom, vec![prefab], 1));
+    let map = sim.resources.get::<Map>().unwrap();
+
+    let start = map.spawns[0].pos;
+    let player = sim.resources.get::<PlayerPosition>().unwrap().0;
+    assert_eq!(player, start);
+
+    let room = map.area_at(start).unwrap();
+    assert!(enemies(&sim).iter().all(|&(p, _)| !room.contains(p)));
+}
+
+#[test]
+fn prefabs_in_caves_are_not_the_start_room() {
+    let prefab = Prefab::parse("guarded", "#####\n#$C.#\n##.##\n").unwrap();
+    let monster_c = EntityType::Monster(MonsterId::new("MonsterC").unwrap());
+
+    let mut checked = 0;
+    for seed in 0..10 {
+        let sim = first_level(
+            &seed.to_string(),
+            WithPrefabs::new(Caves::default(), vec![prefab.clone()], 1),
+        );
+        let map = sim.resources.get::<Map>().unwrap();
+        assert!(map.rooms.is_empty());
+        let [area] = map.prefab_areas[..] else {
+            continue;
+        };
+
+        // the start is picked from the whole cave, now and then that's
+        // inside the prefab
+        let player = sim.resources.get::<PlayerPosition>().unwrap().0;
+        if area.contains(player) {
+            continue;
+        }
+        let guard = map.spawns[1].pos;
+        assert!(enemies(&sim).contains(&(guard, monster_c)), "seed {}", seed);
+        checked += 1;
+    }
+    assert!(
+        checked >= 5,
+        "only {} seeds had the prefab away from the start",
+        checked
+    );
+}
+
+#[test]
+fn corridors_go_around_earlier_prefabs() {
+    let prefab = Prefab::parse("vault", "#####\n#...#\n#.C.#\n#...#\n#####\n").unwrap();
+    let one = WithPrefabs::new(OpenRoom, vec![prefab.clone()], 1);
+    let two = WithPrefabs::new(OpenRoom, vec![prefab], 2);
+
+    let mut neighbours = 0;
+    for seed in 0..50 {
+        // the second prefab is stamped on the same map the first one left
+        let before = one.generate(16, 12, 1, &mut TheRng::seed_from_u64(seed));
+        let after = two.generate(16, 12, 1, &mut TheRng::seed_from_u64(seed));
+        let [first, _] = after.prefab_areas[..] else {
+            continue;
+        };
+        assert_eq!(before.prefab_areas, [first]);
+
+        for x in first.x..first.x + first.width {
+            for y in first.y..first.y + first.height {
+                let idx = after.idx(x, y);
+                assert_eq!(after.cells[idx], before.cells[idx], "seed {}", seed);
+            }
+        }
+        neighbours += 1;
+    }
+    assert!(neighbours >= 25, "only {} maps had two prefabs", neighbours);
+}
@@ -0,0 +1,71 @@
+use centsyl::{
+    components::MapPosition,
+    game::TheRng,
+    map::{Map, MapViewport, Projection},
+};
+use rand::SeedableRng;
+
+fn viewport() -> MapViewport {
+    let map = Map::new(40, 30, &mut TheRng::seed_from_u64(0));
+    let mut viewport = MapViewport::new(20, 10, &map);
+    viewport.move_offset(7.0, 5.0);
+    viewport
+}
+
+#[test]
+fn screen_to_map_inverts_map_to_screen() {
+    let viewport = viewport();
+
+    // the middle of each tile on screen maps back to the tile
+    for (projection, (mx, my)) in [
+        (Projection::TopDown, (8, 8)),
+        (Projection::Isometric, (8, 4)),
+    ] {
+        for x in 0..40 {
+            for y in 0..30 {
+                let pos = MapPosition { x, y };
+                let screen = viewport.map_to_screen_pos(pos, 16.0, projection);
+                assert_eq!(
+                    viewport.screen_to_map_pos(screen.x + mx, screen.y + my, 16.0, projection),
+                    pos,
+                    "{:?}",
+                    projection
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn isometric_tiles_are_diamonds() {
+    let viewport = viewport();
+    let at = |x, y| viewport.map_to_screen_pos(MapPosition { x, y }, 16.0, Projection::Isometric);
+
+    // a step east goes down right, a step south goes down left
+    let origin = at(10, 10);
+    let east = at(11, 10);
+    let south = at(10, 11);
+    assert_eq!((east.x - origin.x, east.y - origin.y), (8, 4));
+    assert_eq!((south.x - origin.x, south.y - origin.y), (-8, 4));
+
+    // the pixel just above a diamond's left corner belongs to the tile behind,
+    // the one next to it to the diamond
+    assert_eq!(
+        viewport.screen_to_map_pos(origin.x, origin.y + 3, 16.0, Projection::Isometric),
+        MapPosition { x: 9, y: 10 }
+    );
+    assert_eq!(
+        viewport.screen_to_map_pos(origin.x + 1, origin.y + 4, 16.0, Projection::Isometric),
+        MapPosition { x: 10, y: 10 }
+    );
+}
+
+#[test]
+fn offset_round_trips() {
+    for projection in [Projection::TopDown, Projection::Isometric] {
+        for (x, y) in [(16.0, 0.0), (0.0, -16.0), (6.0, 3.0), (-2.5, 8.0)] {
+            let (sx, sy) = projection.offset(x, y);
+            assert_eq!(projection.unoffset(sx, sy), (x, y));
+        }
+    }
+}
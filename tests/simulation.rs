@@ -0,0 +1,201 @@
+use centsyl::{
+    components::*,
+    data::{SCREEN_HEIGHT, SCREEN_WIDTH},
+    game::{GameState, TheRng},
+    input::{GameAction, TickInput},
+    map::Map,
+    render::render_frame,
+    replay::Replay,
+    rng::RunSeed,
+    save::{load_game, save_game},
+    simulation::Simulation,
+    update::UpdateResult,
+};
+use legion::*;
+use rand::SeedableRng;
+
+#[test]
+fn runs_headless() {
+    let mut sim = Simulation::new(TheRng::seed_from_u64(1));
+
+    sim.step(&TickInput::default());
+    assert_eq!(sim.game_state(), GameState::GenerateNewMap(1));
+    sim.step(&TickInput::default());
+    assert_eq!(sim.game_state(), GameState::Running);
+    assert!(sim.resources.get::<Map>().is_some());
+
+    let input = TickInput::default().hold(GameAction::MoveRight);
+    for _ in 0..100 {
+        sim.step(&input);
+    }
+
+    let mut query = <(&PlayerFlag, &Health)>::query();
+    assert!(query.iter(&sim.world).count() == 1);
+
+    let quit = TickInput::default().press(GameAction::Quit);
+    assert!(matches!(sim.step(&quit), UpdateResult::Exit));
+}
+
+#[test]
+fn renders_headless() {
+    let mut sim = Simulation::new(TheRng::seed_from_u64(1));
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+
+    let frame = render_frame(&mut sim);
+    assert_eq!(frame.dimensions(), (SCREEN_WIDTH, SCREEN_HEIGHT));
+    assert!(frame.pixels().any(|p| p.0[3] == 0xff));
+}
+
+// walks in a square and fires every now and then
+fn scripted_input(tick: usize) -> TickInput {
+    let moves = [
+        GameAction::MoveRight,
+        GameAction::MoveDown,
+        GameAction::MoveLeft,
+        GameAction::MoveUp,
+    ];
+    let input = TickInput::default().hold(moves[(tick / 50) % moves.len()]);
+    if tick.is_multiple_of(40) {
+        input.press(GameAction::Fire)
+    } else {
+        input
+    }
+}
+
+fn positions(sim: &Simulation) -> Vec<(i32, i32)> {
+    let mut query = <&MapPosition>::query();
+    query.iter(&sim.world).map(|p| (p.x, p.y)).collect()
+}
+
+#[test]
+fn same_seed_same_run() {
+    fn run(seed: &str) -> Vec<(i32, i32)> {
+        let mut sim = Simulation::from_seed(seed).unwrap();
+        for tick in 0..1000 {
+            sim.step(&scripted_input(tick));
+        }
+        positions(&sim)
+    }
+
+    assert_eq!(run("1234"), run("1234"));
+
+    let json = serde_json::to_string(&TheRng::seed_from_u64(1234)).unwrap();
+    assert_eq!(run("1234"), run(&json));
+}
+
+#[test]
+fn replay_reproduces_run() {
+    let mut sim = Simulation::from_seed("99").unwrap();
+    let mut recording = Replay::for_simulation(&sim);
+    for tick in 0..1000 {
+        let input = scripted_input(tick);
+        recording.record(input);
+        sim.step(&input);
+    }
+
+    let mut bytes = Vec::new();
+    recording.write(&mut bytes).unwrap();
+    let replay = Replay::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(replay, recording);
+    assert_eq!(replay.len(), 1000);
+
+    let mut replayed = replay.start().unwrap();
+    for input in replay.inputs() {
+        replayed.step(&input);
+    }
+
+    assert_eq!(replayed.tick, sim.tick);
+    assert_eq!(replayed.game_state(), sim.game_state());
+    assert_eq!(positions(&replayed), positions(&sim));
+}
+
+#[test]
+fn replays_note_the_data_files() {
+    // tests run in the crate root, next to the built-in monsters.json
+    assert!(Replay::new("3")
+        .data
+        .iter()
+        .any(|(path, _)| path == "monsters.json"));
+
+    let mut recording = Replay::new("3");
+    recording.data = vec![
+        ("monsters.json".to_owned(), 1),
+        ("levels.json".to_owned(), 2),
+    ];
+    let mut bytes = Vec::new();
+    recording.write(&mut bytes).unwrap();
+    let replay = Replay::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(replay, recording);
+
+    assert!(replay.changed_data(&replay.data).is_empty());
+    let now = [
+        ("monsters.json".to_owned(), 1),
+        ("animations.json".to_owned(), 3),
+        ("levels.json".to_owned(), 4),
+    ];
+    assert_eq!(
+        replay.changed_data(&now),
+        ["animations.json", "levels.json"]
+    );
+    assert_eq!(replay.changed_data(&[]), ["levels.json", "monsters.json"]);
+}
+
+#[test]
+fn loaded_game_continues_like_the_saved_one() {
+    let mut sim = Simulation::from_seed("7").unwrap();
+    for tick in 0..600 {
+        sim.step(&scripted_input(tick));
+    }
+
+    let mut loaded = load_game(&save_game(&sim).unwrap()).unwrap();
+    assert_eq!(loaded.tick, sim.tick);
+    assert_eq!(loaded.game_state(), sim.game_state());
+    assert_eq!(positions(&loaded), positions(&sim));
+
+    for tick in 600..1200 {
+        sim.step(&scripted_input(tick));
+        loaded.step(&scripted_input(tick));
+    }
+
+    assert_eq!(loaded.game_state(), sim.game_state());
+    assert_eq!(positions(&loaded), positions(&sim));
+    assert_eq!(
+        serde_json::to_string(&*loaded.resources.get::<TheRng>().unwrap()).unwrap(),
+        serde_json::to_string(&*sim.resources.get::<TheRng>().unwrap()).unwrap()
+    );
+}
+
+#[test]
+fn restarts_show_the_seed_of_the_new_run() {
+    let mut sim = Simulation::from_seed("5").unwrap();
+    for tick in 0..300 {
+        sim.step(&scripted_input(tick));
+    }
+
+    let mut query = <(&PlayerFlag, &mut Health)>::query();
+    for (_, health) in query.iter_mut(&mut sim.world) {
+        health.0 = 0;
+    }
+    sim.step(&TickInput::default());
+    assert_eq!(sim.game_state(), GameState::PlayerDead);
+
+    sim.step(&TickInput::default().press(GameAction::Confirm));
+    assert_eq!(sim.game_state(), GameState::Init);
+    let seed = sim.resources.get::<RunSeed>().unwrap().0.clone();
+    assert_ne!(seed, "5");
+
+    let mut fresh = Simulation::from_seed(&seed).unwrap();
+    for tick in 0..1000 {
+        sim.step(&scripted_input(tick));
+        fresh.step(&scripted_input(tick));
+    }
+
+    assert_eq!(sim.game_state(), fresh.game_state());
+    assert_eq!(positions(&sim), positions(&fresh));
+    assert_eq!(
+        serde_json::to_string(&*sim.resources.get::<TheRng>().unwrap()).unwrap(),
+        serde_json::to_string(&*fresh.resources.get::<TheRng>().unwrap()).unwrap()
+    );
+}
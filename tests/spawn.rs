@@ -0,0 +1,96 @@
+use centsyl::{
+    components::*,
+    game::{GameState, TheRng},
+    input::TickInput,
+    map::{Map, TileType},
+    monsters::Monsters,
+    resources::PlayerPosition,
+    simulation::Simulation,
+    spawn::{player_start, spawn_enemy, spawn_items, MapFull},
+    tiled::LevelList,
+};
+use legion::*;
+use rand::SeedableRng;
+
+#[test]
+fn sealed_pockets_are_filled_in() {
+    let mut rng = TheRng::seed_from_u64(2);
+    for _ in 0..50 {
+        let mut map = Map::new(30, 20, &mut rng);
+        map.update_blocks_movement();
+        map.keep_largest_region();
+
+        assert_eq!(map.regions().len(), 1);
+        let open = (0..map.cells.len())
+            .filter(|&idx| !map.blocks_movement[idx])
+            .collect::<Vec<_>>();
+        assert_eq!(map.reachable, open);
+    }
+}
+
+#[test]
+fn spawning_on_a_full_map_is_an_error() {
+    let mut rng = TheRng::seed_from_u64(0);
+
+    // a corridor of three floor tiles
+    let mut map = Map::filled(5, 3, TileType::Wall);
+    for x in 1..4 {
+        let idx = map.idx(x, 1);
+        map.cells[idx] = TileType::Floor;
+    }
+    map.update_blocks_movement();
+    map.keep_largest_region();
+
+    let mut world = World::default();
+    let player_pos = MapPosition { x: 1, y: 1 };
+    let monsters = Monsters::default();
+
+    assert!(spawn_enemy(&mut world, &map, &mut rng, &player_pos, 1, &monsters).is_ok());
+    assert!(spawn_items(&mut world, &map, &mut rng, &player_pos, EntityType::Ammo).is_ok());
+    assert_eq!(
+        spawn_enemy(&mut world, &map, &mut rng, &player_pos, 1, &monsters).err(),
+        Some(MapFull)
+    );
+    assert_eq!(
+        spawn_items(&mut world, &map, &mut rng, &player_pos, EntityType::Health).err(),
+        Some(MapFull)
+    );
+}
+
+#[test]
+fn player_start_needs_an_open_tile() {
+    let mut rng = TheRng::seed_from_u64(0);
+
+    let mut map = Map::filled(5, 3, TileType::Wall);
+    map.update_blocks_movement();
+    map.keep_largest_region();
+    assert_eq!(player_start(&map, &mut rng), Err(MapFull));
+
+    let idx = map.idx(2, 1);
+    map.cells[idx] = TileType::Lava;
+    map.update_blocks_movement();
+    map.keep_largest_region();
+    // somewhere that hurts still beats nowhere
+    assert_eq!(player_start(&map, &mut rng), Ok(MapPosition { x: 2, y: 1 }));
+}
+
+#[test]
+fn levels_without_open_tiles_are_generated_instead() {
+    let mut sim = Simulation::from_seed("9").unwrap();
+    let mut levels = LevelList::default();
+    levels.insert(1, Map::filled(8, 6, TileType::Wall));
+    sim.resources.insert(levels);
+
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+    assert!(sim.game_state() == GameState::Running);
+
+    let map = sim.resources.get::<Map>().unwrap();
+    let player = sim.resources.get::<PlayerPosition>().unwrap().0;
+    assert!(map.is_reachable(player));
+    // spawned like any generated level
+    let mut query = <&EntityType>::query();
+    assert!(query
+        .iter(&sim.world)
+        .any(|t| matches!(t, EntityType::Monster(_))));
+}
@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use centsyl::{
+    components::*,
+    game::GameState,
+    input::TickInput,
+    map::{Map, SpawnKind, SpawnPoint, TileType},
+    monsters::{MonsterId, Monsters},
+    resources::PlayerPosition,
+    simulation::Simulation,
+    tiled::{self, LevelList, TiledError},
+};
+use legion::*;
+
+fn levels_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/levels")
+}
+
+fn check_small(map: &Map) {
+    assert_eq!((map.width, map.height), (8, 6));
+    assert_eq!(map.cells[map.idx(1, 1)], TileType::Floor);
+    assert_eq!(map.cells[map.idx(3, 2)], TileType::Wall);
+    assert_eq!(map.cells[map.idx(0, 3)], TileType::Wall);
+
+    assert_eq!(
+        map.spawns,
+        [
+            SpawnPoint {
+                pos: MapPosition { x: 1, y: 1 },
+                kind: SpawnKind::PlayerStart,
+            },
+            SpawnPoint {
+                pos: MapPosition { x: 6, y: 4 },
+                kind: SpawnKind::Enemy(MonsterId::new("MonsterC")),
+            },
+            SpawnPoint {
+                pos: MapPosition { x: 5, y: 1 },
+                kind: SpawnKind::Item(Some(EntityType::Ammo)),
+            },
+        ]
+    );
+}
+
+#[test]
+fn json_and_tmx_load_the_same_level() {
+    let json = tiled::load_map(levels_dir().join("small.tmj"), &Monsters::default()).unwrap();
+    let tmx = tiled::load_map(levels_dir().join("small.tmx"), &Monsters::default()).unwrap();
+
+    check_small(&json);
+    check_small(&tmx);
+    assert!(json.cells == tmx.cells);
+}
+
+#[test]
+fn compressed_layers_are_rejected() {
+    let xml = r#"<map width="3" height="3" tilewidth="16" tileheight="16">
+        <layer name="Floor"><data encoding="base64" compression="zlib">eJw=</data></layer>
+    </map>"#;
+
+    assert!(matches!(
+        tiled::parse_tmx(xml, &Monsters::default()),
+        Err(TiledError::Format(_))
+    ));
+}
+
+#[test]
+fn maps_without_open_tiles_are_rejected() {
+    let xml = r#"<map width="3" height="3" tilewidth="16" tileheight="16">
+        <layer name="Ground"><data encoding="csv">1,1,1,1,1,1,1,1,1</data></layer>
+    </map>"#;
+
+    match tiled::parse_tmx(xml, &Monsters::default()) {
+        Err(TiledError::Format(msg)) => assert!(msg.contains("no open tiles"), "{}", msg),
+        other => panic!("expected a format error, got {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn object_classes_are_looked_up_in_the_monsters() {
+    let xml = r#"<map width="4" height="3" tilewidth="16" tileheight="16">
+        <layer name="Floor"><data encoding="csv">0,0,0,0,0,1,1,0,0,0,0,0</data></layer>
+        <objectgroup>
+            <object class="MonsterA" x="16" y="16"/>
+            <object class="Brute" x="32" y="16"/>
+            <object name="chest" x="32" y="16"/>
+        </objectgroup>
+    </map>"#;
+    let monster = |name| SpawnKind::Enemy(MonsterId::new(name));
+    let kinds = |monsters: &Monsters| {
+        let map = tiled::parse_tmx(xml, monsters).unwrap();
+        map.spawns.iter().map(|s| s.kind).collect::<Vec<_>>()
+    };
+
+    assert_eq!(kinds(&Monsters::default()), [monster("MonsterA")]);
+
+    let brute = Monsters::from_json(
+        r#"[{
+            "id": "Brute",
+            "health": 7,
+            "speed": { "sides": 6 },
+            "sight": 3,
+            "spawn": [{ "from_level": 1, "weight": 1 }],
+            "frames": { "stationary": [{ "x": 12, "y": 3 }], "moving": [{ "x": 14, "y": 3 }] }
+        }]"#,
+    )
+    .unwrap();
+    assert_eq!(kinds(&brute), [monster("Brute")]);
+}
+
+#[test]
+fn authored_levels_replace_generated_ones() {
+    let mut sim = Simulation::from_seed("9").unwrap();
+    sim.resources
+        .insert(LevelList::load(levels_dir().join("levels.json"), &Monsters::default()).unwrap());
+
+    sim.step(&TickInput::default());
+    sim.step(&TickInput::default());
+    assert!(sim.game_state() == GameState::Running);
+
+    let player = sim.resources.get::<PlayerPosition>().unwrap().0;
+    assert_eq!(player, MapPosition { x: 1, y: 1 });
+
+    // only what the level places, nothing random on top
+    let mut query = <(&MapPosition, &EntityType)>::query();
+    let mut placed = query
+        .iter(&sim.world)
+        .map(|(&p, &t)| (p.x, p.y, t))
+        .collect::<Vec<_>>();
+    placed.sort_by_key(|&(x, y, _)| (x, y));
+    assert_eq!(
+        placed,
+        [
+            (1, 1, EntityType::Player),
+            (5, 1, EntityType::Ammo),
+            (
+                6,
+                4,
+                EntityType::Monster(MonsterId::new("MonsterC").unwrap())
+            ),
+        ]
+    );
+
+    // level 2 has no file and is generated
+    sim.resources.insert(GameState::GenerateNewMap(2));
+    sim.step(&TickInput::default());
+    let map = sim.resources.get::<Map>().unwrap();
+    assert_eq!((map.width, map.height), (12, 8));
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use centsyl::{
+    components::*,
+    data::HAZARD_DAMAGE_TICKS,
+    events::GameEvent,
+    input::{GameAction, TickInput},
+    map::{Map, TileType},
+    simulation::Simulation,
+};
+use common::{idle, run};
+use legion::*;
+
+fn set_tile(sim: &mut Simulation, x: i32, y: i32, tile: TileType) {
+    let mut map = sim.resources.get_mut::<Map>().unwrap();
+    let idx = map.idx(x, y);
+    map.set_tile(idx, tile);
+}
+
+fn tile(sim: &Simulation, x: i32, y: i32) -> TileType {
+    let map = sim.resources.get::<Map>().unwrap();
+    map.cells[map.idx(x, y)]
+}
+
+fn component<T: legion::storage::Component, R>(
+    sim: &Simulation,
+    entity: Entity,
+    f: impl Fn(&T) -> R,
+) -> R {
+    f(sim
+        .world
+        .entry_ref(entity)
+        .unwrap()
+        .get_component::<T>()
+        .unwrap())
+}
+
+#[test]
+fn bumping_a_closed_door_opens_it() {
+    let (mut sim, _monster) = common::scene();
+    set_tile(&mut sim, 3, 2, TileType::DoorClosed);
+
+    let right = TickInput::default().hold(GameAction::MoveRight);
+    let events = run(&mut sim, std::iter::once(right).chain(idle(10)));
+
+    assert_eq!(
+        events,
+        vec![GameEvent::DoorOpened {
+            pos: MapPosition { x: 3, y: 2 }
+        }]
+    );
+    assert_eq!(tile(&sim, 3, 2), TileType::DoorOpen);
+    let player = sim.entities.player;
+    assert_eq!(component(&sim, player, |p: &MapPosition| p.x), 2);
+
+    run(&mut sim, std::iter::once(right).chain(idle(10)));
+    assert_eq!(component(&sim, player, |p: &MapPosition| p.x), 3);
+}
+
+#[test]
+fn wading_through_water_takes_longer() {
+    let (mut sim, _monster) = common::scene();
+    set_tile(&mut sim, 3, 2, TileType::Water);
+    let player = sim.entities.player;
+
+    let right = TickInput::default().hold(GameAction::MoveRight);
+    run(&mut sim, std::iter::once(right).chain(idle(10)));
+    assert_eq!(component(&sim, player, |p: &MapPosition| p.x), 2);
+
+    run(&mut sim, idle(10));
+    assert_eq!(component(&sim, player, |p: &MapPosition| p.x), 3);
+}
+
+#[test]
+fn standing_in_lava_hurts_every_so_often() {
+    let (mut sim, _monster) = common::scene();
+    set_tile(&mut sim, 2, 2, TileType::Lava);
+    let player = sim.entities.player;
+    let health = component(&sim, player, |h: &Health| h.0);
+
+    let events = run(&mut sim, idle(2 * HAZARD_DAMAGE_TICKS as usize + 2));
+
+    let hits = events
+        .iter()
+        .filter(|e| matches!(e, GameEvent::PlayerDamaged { amount: 1, .. }))
+        .count();
+    assert_eq!(hits, 3);
+    assert_eq!(component(&sim, player, |h: &Health| h.0), health - 3);
+}
+
+#[test]
+fn projectiles_knock_down_cracked_walls() {
+    let (mut sim, monster) = common::scene();
+    set_tile(&mut sim, 4, 2, TileType::CrackedWall);
+
+    let fire = TickInput::default().press(GameAction::FireRight);
+    let events = run(&mut sim, std::iter::once(fire).chain(idle(30)));
+
+    assert!(events.contains(&GameEvent::WallDestroyed {
+        pos: MapPosition { x: 4, y: 2 },
+    }));
+    assert!(!events
+        .iter()
+        .any(|e| matches!(e, GameEvent::ProjectileHit { .. })));
+    assert_eq!(tile(&sim, 4, 2), TileType::Rubble);
+    assert_eq!(component(&sim, monster, |h: &Health| h.0), 3);
+
+    let map = sim.resources.get::<Map>().unwrap();
+    assert!(!map.blocks_movement[map.idx(4, 2)]);
+    assert!(!map.blocks_visibility[map.idx(4, 2)]);
+}
+
+#[test]
+fn closed_doors_connect_regions_without_being_reachable() {
+    let mut map = Map::filled(7, 3, TileType::Wall);
+    for x in 1..6 {
+        let idx = map.idx(x, 1);
+        map.cells[idx] = TileType::Floor;
+    }
+    let door = map.idx(3, 1);
+    map.cells[door] = TileType::DoorClosed;
+    map.update_blocks_movement();
+    map.keep_largest_region();
+
+    assert_eq!(map.cells[map.idx(1, 1)], TileType::Floor);
+    assert_eq!(map.cells[map.idx(5, 1)], TileType::Floor);
+    assert!(map.is_reachable(MapPosition { x: 1, y: 1 }));
+    assert!(map.is_reachable(MapPosition { x: 5, y: 1 }));
+    assert!(!map.is_reachable(MapPosition { x: 3, y: 1 }));
+    assert!(map.blocks_visibility[door]);
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use centsyl::input::{GameAction, InputLatch, TickInput};
+use centsyl::timestep::FixedTimestep;
+
+fn ticks(timestep: &mut FixedTimestep) -> u32 {
+    let mut n = 0;
+    while timestep.tick() {
+        n += 1;
+    }
+    n
+}
+
+#[test]
+fn tick_rate_is_independent_of_frame_rate() {
+    let second = Duration::from_secs(1);
+
+    for frames in [30, 60, 144, 1000] {
+        let mut timestep = FixedTimestep::new(60);
+        let total: u32 = (0..frames)
+            .map(|_| {
+                timestep.advance(second / frames);
+                ticks(&mut timestep)
+            })
+            .sum();
+
+        // float rounding of the frame time may leave the last tick short
+        assert!(
+            (59..=60).contains(&total),
+            "{} fps ran {} ticks",
+            frames,
+            total
+        );
+    }
+}
+
+#[test]
+fn leftover_time_is_the_interpolation_alpha() {
+    let mut timestep = FixedTimestep::new(50);
+
+    timestep.advance(Duration::from_millis(50));
+    assert_eq!(ticks(&mut timestep), 2);
+    assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+}
+
+#[test]
+fn long_stalls_are_not_caught_up() {
+    let mut timestep = FixedTimestep::new(60);
+
+    timestep.advance(Duration::from_secs(10));
+    assert!(ticks(&mut timestep) <= 8);
+}
+
+#[test]
+fn presses_land_in_exactly_one_tick() {
+    let mut latch = InputLatch::default();
+
+    // pressed and released on frames that ran no tick
+    latch.latch(TickInput::default().press(GameAction::FireUp));
+    latch.latch(TickInput::default());
+
+    let first = latch.take();
+    assert!(first.pressed(GameAction::FireUp));
+    assert!(first.held(GameAction::FireUp));
+    assert_eq!(latch.take(), TickInput::default());
+
+    // held keys carry over to every tick
+    latch.latch(TickInput::default().hold(GameAction::MoveLeft));
+    assert!(latch.take().held(GameAction::MoveLeft));
+    assert!(latch.take().held(GameAction::MoveLeft));
+}